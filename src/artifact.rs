@@ -0,0 +1,531 @@
+use crate::build::build_test_target;
+use crate::diff::{diff_lines, DiffLine};
+use crate::voter::plain_toolkit;
+use crate::{
+    get_tests_with, referendum_dir, toolkit_dir, vote, ReferendumError, Result, RunOptions,
+};
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use string_builder::Builder;
+
+//unchanged lines kept around each change in a function diff
+const CONTEXT: usize = 3;
+
+//diff_lines needs a table cell per pair of lines, larger functions are only reported as changed
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+//one codegen unit keeps every function of the crate in a single .s and .ll file, and v0
+//mangling names generic instantiations the same way on every toolkit
+const EMIT: [&str; 5] = [
+    "--emit=asm,llvm-ir,link",
+    "-C",
+    "codegen-units=1",
+    "-C",
+    "symbol-mangling-version=v0",
+];
+
+#[derive(Debug)]
+pub struct Artifacts {
+    pub asm: String,
+    pub llvm_ir: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDiff {
+    pub name: String,
+    pub added: usize,
+    pub removed: usize,
+    pub diff: String,
+}
+
+impl FunctionDiff {
+    pub fn size(&self) -> usize {
+        self.added + self.removed
+    }
+}
+
+#[derive(Debug)]
+pub struct ArtifactDiff {
+    pub kind: String,
+    pub toolkit: String,
+    pub functions: Vec<FunctionDiff>,
+}
+
+#[derive(Debug)]
+pub struct ArtifactDifferential {
+    pub test: String,
+    pub reference: String,
+    pub diffs: Vec<ArtifactDiff>,
+}
+
+fn artifact_error(e: impl std::fmt::Display) -> ReferendumError {
+    ReferendumError::ArtifactFailure(e.to_string())
+}
+
+fn compile(patterns: &[(&str, &'static str)]) -> Vec<(Regex, &'static str)> {
+    patterns
+        .iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), *replacement))
+        .collect()
+}
+
+fn apply(line: &str, replacements: &[(Regex, &str)]) -> String {
+    replacements
+        .iter()
+        .fold(line.to_string(), |line, (re, replacement)| {
+            re.replace_all(&line, *replacement).to_string()
+        })
+}
+
+//hashes in mangled names change with every compiler, so symbols are compared by path
+pub fn demangle_symbols(line: &str) -> String {
+    let re = Regex::new(r"_?_ZN[0-9A-Za-z_$.]*E|_R[0-9A-Za-z_]+").unwrap();
+    re.replace_all(
+        line,
+        |caps: &regex::Captures| match rustc_demangle::try_demangle(&caps[0]) {
+            Ok(symbol) => format!("{:#}", symbol),
+            Err(_) => caps[0].to_string(),
+        },
+    )
+    .to_string()
+}
+
+//generic instantiations demangle to the same path, later ones are numbered
+fn insert_function(functions: &mut BTreeMap<String, String>, name: &str, body: &[String]) {
+    let name = demangle_symbols(name.trim_matches('"'));
+    let mut key = name.clone();
+    let mut n = 1;
+    while functions.contains_key(&key) {
+        n += 1;
+        key = format!("{} ({})", name, n);
+    }
+    functions.insert(key, body.join("\n"));
+}
+
+pub fn parse_asm_functions(asm: &str) -> BTreeMap<String, String> {
+    let label_re = Regex::new(r#"^("[^"]+"|[A-Za-z_$][\w$.]*):"#).unwrap();
+    let type_re = Regex::new(r#"^\.type\s+("[^"]+"|[^,\s]+),\s*@function"#).unwrap();
+    let local_re = Regex::new(r"^\.?L(tmp|func_begin|func_end)\d+:").unwrap();
+    //block and temporary labels are numbered across the whole crate
+    let replacements = compile(&[
+        (r"\.?LBB\d+_(\d+)", ".LBB_$1"),
+        (r"\.?Ltmp\d+", ".Ltmp"),
+        (r"\.Lalloc_[0-9a-f]+", ".Lalloc"),
+        (r"\.Lanon\.[0-9a-f]+\.(\d+)", ".Lanon.$1"),
+        (r"\s+", " "),
+    ]);
+
+    let mut functions = BTreeMap::new();
+    let mut typed: BTreeSet<String> = BTreeSet::new();
+    let mut current: Option<(String, Vec<String>, bool)> = None;
+    for line in asm.lines() {
+        if let Some(caps) = label_re.captures(line) {
+            let name = caps[1].to_string();
+            if !name.starts_with('L') && !name.starts_with("l_") {
+                if let Some((name, body, true)) = current.take() {
+                    insert_function(&mut functions, &name, &body);
+                }
+                let is_function = typed.contains(&name);
+                current = Some((name, Vec::new(), is_function));
+                continue;
+            }
+        }
+
+        let trimmed = line.split('#').next().unwrap().trim();
+        if let Some(caps) = type_re.captures(trimmed) {
+            typed.insert(caps[1].to_string());
+        }
+        let (_, body, is_function) = match current.as_mut() {
+            Some(current) => current,
+            None => continue,
+        };
+        if trimmed == ".cfi_startproc" {
+            *is_function = true;
+        }
+        if trimmed == ".cfi_endproc"
+            || trimmed.starts_with(".size")
+            || trimmed.starts_with(".section")
+        {
+            if let Some((name, body, true)) = current.take() {
+                insert_function(&mut functions, &name, &body);
+            }
+            continue;
+        }
+        if trimmed.is_empty()
+            || trimmed.starts_with(".loc")
+            || trimmed.starts_with(".cfi_")
+            || trimmed.starts_with(".p2align")
+            || trimmed.starts_with(".file")
+            || local_re.is_match(trimmed)
+        {
+            continue;
+        }
+        body.push(demangle_symbols(&apply(trimmed, &replacements)));
+    }
+    if let Some((name, body, true)) = current {
+        insert_function(&mut functions, &name, &body);
+    }
+    functions
+}
+
+pub fn parse_llvm_ir_functions(ir: &str) -> BTreeMap<String, String> {
+    let define_re = Regex::new(r#"^define .*?@("[^"]+"|[\w$.\-]+)\("#).unwrap();
+    //debug metadata and attribute group numbers are assigned across the whole module
+    let replacements = compile(&[(r",?\s*![\w.]+ !\d+", ""), (r"\s#\d+", "")]);
+
+    let mut functions = BTreeMap::new();
+    let mut current: Option<(String, Vec<String>)> = None;
+    for line in ir.lines() {
+        if let Some(caps) = define_re.captures(line) {
+            current = Some((caps[1].to_string(), Vec::new()));
+        }
+        let (name, body) = match current.as_mut() {
+            Some(current) => current,
+            None => continue,
+        };
+        let trimmed = line.trim();
+        if trimmed.starts_with("#dbg_") || trimmed.starts_with("call void @llvm.dbg.") {
+            continue;
+        }
+        body.push(demangle_symbols(&apply(line.trim_end(), &replacements)));
+        if trimmed == "}" {
+            let (name, body) = (name.clone(), body.clone());
+            insert_function(&mut functions, &name, &body);
+            current = None;
+        }
+    }
+    functions
+}
+
+//unified diff with only the lines near a change, functions can be thousands of lines long
+fn context_diff(lines: &[DiffLine]) -> String {
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Equal(_)))
+        .map(|(i, _)| i)
+        .collect();
+    let near = |i: usize| {
+        changed
+            .iter()
+            .any(|c| i + CONTEXT >= *c && i <= c + CONTEXT)
+    };
+
+    let mut diff = String::new();
+    let mut skipped = false;
+    for (i, line) in lines.iter().enumerate() {
+        if !near(i) {
+            skipped = true;
+            continue;
+        }
+        if skipped {
+            diff.push_str("...\n");
+            skipped = false;
+        }
+        diff.push_str(&match line {
+            DiffLine::Equal(text) => format!(" {}\n", text),
+            DiffLine::Removed(text) => format!("-{}\n", text),
+            DiffLine::Added(text) => format!("+{}\n", text),
+        });
+    }
+    if skipped {
+        diff.push_str("...\n");
+    }
+    diff
+}
+
+//changed functions ranked by the number of changed lines, largest first
+pub fn diff_functions(
+    reference: &BTreeMap<String, String>,
+    dissent: &BTreeMap<String, String>,
+) -> Vec<FunctionDiff> {
+    let names: BTreeSet<&String> = reference.keys().chain(dissent.keys()).collect();
+    let mut functions: Vec<FunctionDiff> = names
+        .into_iter()
+        .filter_map(|name| {
+            let old = reference.get(name).map(|body| body.as_str()).unwrap_or("");
+            let new = dissent.get(name).map(|body| body.as_str()).unwrap_or("");
+            let (old_len, new_len) = (old.lines().count(), new.lines().count());
+            if old_len * new_len > MAX_DIFF_CELLS {
+                return match old == new {
+                    true => None,
+                    false => Some(FunctionDiff {
+                        name: name.clone(),
+                        added: new_len,
+                        removed: old_len,
+                        diff: format!(
+                            "function changed ({} vs {} lines), too large to diff\n",
+                            old_len, new_len
+                        ),
+                    }),
+                };
+            }
+            let lines = diff_lines(old, new);
+            let added = lines
+                .iter()
+                .filter(|line| matches!(line, DiffLine::Added(_)))
+                .count();
+            let removed = lines
+                .iter()
+                .filter(|line| matches!(line, DiffLine::Removed(_)))
+                .count();
+            match added + removed {
+                0 => None,
+                _ => Some(FunctionDiff {
+                    name: name.clone(),
+                    added,
+                    removed,
+                    diff: context_diff(&lines),
+                }),
+            }
+        })
+        .collect();
+    functions.sort_by(|a, b| b.size().cmp(&a.size()).then_with(|| a.name.cmp(&b.name)));
+    functions
+}
+
+pub fn emit_artifacts(toolkit: &str, selection: &[String]) -> Result<Artifacts> {
+    let options = RunOptions {
+        env: vec![(
+            "CARGO_TARGET_DIR".to_string(),
+            referendum_dir()
+                .join("artifacts")
+                .join(toolkit_dir(toolkit))
+                .to_string_lossy()
+                .to_string(),
+        )],
+        ..Default::default()
+    };
+    let args: Vec<String> = EMIT.iter().map(|arg| arg.to_string()).collect();
+    let build = build_test_target(toolkit, selection, &args, &options)?;
+    let executable = match build.executables.first() {
+        Some(executable) => executable,
+        None => {
+            return Err(artifact_error(format!(
+                "{} did not build a test executable",
+                toolkit
+            )))
+        }
+    };
+    let read = |extension: &str| {
+        let path = executable.with_extension(extension);
+        fs::read_to_string(&path)
+            .map_err(|e| artifact_error(format!("{}: {}", path.to_string_lossy(), e)))
+    };
+    Ok(Artifacts {
+        asm: read("s")?,
+        llvm_ir: read("ll")?,
+    })
+}
+
+pub fn diff_artifacts(
+    toolkits: &[&str],
+    test: &str,
+    selection: &[String],
+) -> Result<ArtifactDifferential> {
+    for toolkit in toolkits.iter() {
+        plain_toolkit(toolkit, "artifact-diff")?;
+    }
+    let options = RunOptions {
+        filter: Some(test.to_string()),
+        ..Default::default()
+    };
+    let tests = get_tests_with(toolkits.to_vec(), &options)?
+        .into_iter()
+        .filter(|found| found.name == test)
+        .collect();
+    let votes = vote(tests)?;
+    let reference = match votes.matches.first() {
+        Some(consensus) => consensus.toolkit.clone(),
+        None => {
+            return Err(artifact_error(format!(
+                "{} has no consensus to compare against",
+                test
+            )))
+        }
+    };
+    if votes.non_matches.is_empty() {
+        return Err(artifact_error(format!("every toolkit agrees on {}", test)));
+    }
+
+    let consensus = emit_artifacts(&plain_toolkit(&reference, "artifact-diff")?, selection)?;
+    let consensus_asm = parse_asm_functions(&consensus.asm);
+    let consensus_ir = parse_llvm_ir_functions(&consensus.llvm_ir);
+    let mut diffs = Vec::new();
+    for dissent in votes.non_matches.iter() {
+        let artifacts = emit_artifacts(
+            &plain_toolkit(&dissent.toolkit, "artifact-diff")?,
+            selection,
+        )?;
+        diffs.push(ArtifactDiff {
+            kind: "asm".to_string(),
+            toolkit: dissent.toolkit.clone(),
+            functions: diff_functions(&consensus_asm, &parse_asm_functions(&artifacts.asm)),
+        });
+        diffs.push(ArtifactDiff {
+            kind: "llvm-ir".to_string(),
+            toolkit: dissent.toolkit.clone(),
+            functions: diff_functions(&consensus_ir, &parse_llvm_ir_functions(&artifacts.llvm_ir)),
+        });
+    }
+
+    Ok(ArtifactDifferential {
+        test: test.to_string(),
+        reference,
+        diffs,
+    })
+}
+
+pub fn get_artifact_differential_results(
+    differential: &ArtifactDifferential,
+    limit: usize,
+) -> String {
+    let mut builder = Builder::default();
+    builder.append("Artifact Differential Results...\n");
+    builder.append(format!(
+        "test {} @ consensus from {}\n",
+        differential.test, differential.reference
+    ));
+    for artifact in differential.diffs.iter() {
+        builder.append(format!(
+            "\n{} @ {}: {} functions changed\n",
+            artifact.kind,
+            artifact.toolkit,
+            artifact.functions.len()
+        ));
+        for function in artifact.functions.iter().take(limit) {
+            builder.append(format!(
+                "\t+{} -{}\t{}\n",
+                function.added, function.removed, function.name
+            ));
+        }
+        for function in artifact.functions.iter().take(limit) {
+            builder.append(format!(
+                "\n---- {} @ {} {} ----\n",
+                function.name, artifact.toolkit, artifact.kind
+            ));
+            builder.append(function.diff.clone());
+        }
+        if artifact.functions.len() > limit {
+            builder.append(format!(
+                "\n{} smaller changes not shown\n",
+                artifact.functions.len() - limit
+            ));
+        }
+    }
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols_are_demangled_without_hashes() {
+        assert_eq!(
+            demangle_symbols(
+                "callq _ZN4core3fmt9Arguments3new17hab48cb305604c12eE\ncallq *_RNvNtNtCsjrHSEGnQ3l9_3std2io5stdio6__print@GOTPCREL(%rip)"
+            ),
+            "callq core::fmt::Arguments::new\ncallq *std::io::stdio::_print@GOTPCREL(%rip)"
+        );
+    }
+
+    #[test]
+    fn asm_functions_are_normalized() {
+        let asm = "\t.section\t.text._ZN6sample5tests4adds17hcd9a5e45b2b8cfd0E,\"ax\",@progbits
+\t.p2align\t4
+\t.type\t_ZN6sample5tests4adds17hcd9a5e45b2b8cfd0E,@function
+_ZN6sample5tests4adds17hcd9a5e45b2b8cfd0E:
+.Lfunc_begin27:
+\t.loc\t12 4 0 is_stmt 1
+\t.cfi_startproc
+\tsubq\t$72, %rsp
+.Ltmp170:
+\tjo\t.LBB27_2
+.LBB27_2:
+\tcallq\t_ZN4core3fmt9Arguments3new17hab48cb305604c12eE
+\t.cfi_endproc
+.Lalloc_7bf4d83167a444e3cf51563928dad022:
+\t.ascii\t\"sum \"
+";
+        let functions = parse_asm_functions(asm);
+        assert_eq!(functions.len(), 1);
+        assert_eq!(
+            functions["sample::tests::adds"],
+            "subq $72, %rsp\njo .LBB_2\n.LBB_2:\ncallq core::fmt::Arguments::new"
+        );
+    }
+
+    #[test]
+    fn llvm_ir_functions_drop_metadata() {
+        let ir = "define internal void @_ZN6sample5tests4adds17hcd9a5e45b2b8cfd0E() unnamed_addr #1 !dbg !202 {
+start:
+  %_2 = add i32 2, 2, !dbg !210
+    #dbg_declare(ptr %x, !208, !DIExpression(), !209)
+  ret void, !dbg !211
+}
+declare void @llvm.trap() #4
+";
+        let functions = parse_llvm_ir_functions(ir);
+        assert_eq!(
+            functions["sample::tests::adds"],
+            "define internal void @sample::tests::adds() unnamed_addr {\nstart:\n  %_2 = add i32 2, 2\n  ret void\n}"
+        );
+    }
+
+    #[test]
+    fn context_diff_skips_distant_lines() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10";
+        let new = "1\n2\n3\n4\n5\nfive\n7\n8\n9\n10";
+        assert_eq!(
+            context_diff(&diff_lines(old, new)),
+            "...\n 3\n 4\n 5\n-6\n+five\n 7\n 8\n 9\n...\n"
+        );
+    }
+
+    #[test]
+    fn large_functions_are_not_diffed_line_by_line() {
+        let body = |last: &str| -> String {
+            let mut lines: Vec<String> = (0..2500).map(|i| format!("  mov {}", i)).collect();
+            lines.push(last.to_string());
+            lines.join("\n")
+        };
+        let mut reference = BTreeMap::new();
+        reference.insert("big".to_string(), body("ret"));
+        let mut dissent = reference.clone();
+        assert!(diff_functions(&reference, &dissent).is_empty());
+
+        dissent.insert("big".to_string(), body("ud2"));
+        let functions = diff_functions(&reference, &dissent);
+        assert_eq!(
+            functions[0].diff,
+            "function changed (2501 vs 2501 lines), too large to diff\n"
+        );
+        assert_eq!(functions[0].size(), 5002);
+    }
+
+    #[test]
+    fn functions_are_ranked_by_change_size() {
+        let functions = |entries: &[(&str, &str)]| -> BTreeMap<String, String> {
+            entries
+                .iter()
+                .map(|(name, body)| (name.to_string(), body.to_string()))
+                .collect()
+        };
+        let reference = functions(&[("a", "x\ny"), ("b", "x\ny\nz"), ("same", "x")]);
+        let dissent = functions(&[("a", "x\nw"), ("b", "p\nq\nr"), ("same", "x"), ("new", "x")]);
+        let ranked: Vec<(String, usize)> = diff_functions(&reference, &dissent)
+            .into_iter()
+            .map(|function| (function.name.clone(), function.size()))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![
+                ("b".to_string(), 6),
+                ("a".to_string(), 2),
+                ("new".to_string(), 1)
+            ]
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use crate::toolchain::Toolchain;
+use crate::{
+    generate_test_result_output, get_dissenting_results, hash_outcome, Consensus, ReferendumError,
+    Result, Test,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use string_builder::Builder;
+
+pub const DEFAULT_BASELINE: &str = "referendum-baseline.json";
+
+//baselines are meant to be committed, so they are pretty printed and sorted
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Baseline {
+    pub toolchains: Vec<Toolchain>,
+    pub consensus: BTreeMap<String, Consensus>,
+}
+
+#[derive(Debug)]
+pub struct BaselineCheck {
+    pub matches: Vec<Test>,
+    pub differing: Vec<Test>,
+    pub not_in_baseline: Vec<Test>,
+    pub missing: Vec<String>,
+}
+
+fn baseline_error(e: impl std::fmt::Display) -> ReferendumError {
+    ReferendumError::BaselineFailure(e.to_string())
+}
+
+pub fn save_baseline(path: &Path, baseline: &Baseline) -> Result<()> {
+    let contents = serde_json::to_string_pretty(baseline).map_err(baseline_error)?;
+    fs::write(path, contents + "\n").map_err(baseline_error)
+}
+
+pub fn load_baseline(path: &Path) -> Result<Baseline> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| baseline_error(format!("{}: {}", path.display(), e)))?;
+    serde_json::from_str(&contents).map_err(baseline_error)
+}
+
+pub fn check_baseline(baseline: &Baseline, tests: Vec<Test>) -> BaselineCheck {
+    let mut seen: BTreeSet<String> = BTreeSet::new();
+    let mut matches = Vec::new();
+    let mut differing = Vec::new();
+    let mut not_in_baseline = Vec::new();
+
+    for test in tests {
+        seen.insert(test.name.clone());
+        match baseline.consensus.get(&test.name) {
+            Some(consensus) if hash_outcome(consensus.result, &consensus.output) == test.hash => {
+                matches.push(test)
+            }
+            Some(_) => differing.push(test),
+            None => not_in_baseline.push(test),
+        }
+    }
+
+    let missing = baseline
+        .consensus
+        .keys()
+        .filter(|name| !seen.contains(*name))
+        .cloned()
+        .collect();
+
+    BaselineCheck {
+        matches,
+        differing,
+        not_in_baseline,
+        missing,
+    }
+}
+
+impl BaselineCheck {
+    pub fn is_clean(&self) -> bool {
+        self.differing.is_empty() && self.missing.is_empty()
+    }
+}
+
+pub fn get_baseline_check_results(check: &BaselineCheck, baseline: &Baseline) -> String {
+    let mut builder = Builder::default();
+    builder.append(format!(
+        "Baseline Check Results...\n{} tests match the baseline, {} differ, {} are not in the baseline, {} are missing\n\n",
+        check.matches.len(),
+        check.differing.len(),
+        check.not_in_baseline.len(),
+        check.missing.len()
+    ));
+
+    if !check.differing.is_empty() {
+        builder.append(get_dissenting_results(
+            check.differing.clone(),
+            &baseline.consensus,
+        ));
+        builder.append("\n");
+    }
+
+    if !check.not_in_baseline.is_empty() {
+        builder.append("Tests Not In Baseline...\n");
+        for test in check.not_in_baseline.iter() {
+            builder.append(generate_test_result_output(
+                &test.name,
+                test.result,
+                Some(&test.toolkit),
+            ));
+            builder.append("\n");
+        }
+        builder.append("\n");
+    }
+
+    if !check.missing.is_empty() {
+        builder.append("Baseline Tests Not Run...\n");
+        for name in check.missing.iter() {
+            builder.append(format!("test {}\n", name));
+        }
+    }
+
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toolchain::sample_toolchain;
+    use crate::{generate_consensus_map, sample_test};
+
+    fn baseline() -> Baseline {
+        let votes = vec![
+            sample_test("tests::a", "nightly_1", true, "a"),
+            sample_test("tests::b", "nightly_1", true, "b"),
+            sample_test("tests::c", "nightly_1", true, "c"),
+        ];
+        Baseline {
+            toolchains: vec![sample_toolchain("nightly_1")],
+            consensus: generate_consensus_map(&votes),
+        }
+    }
+
+    #[test]
+    fn check_sorts_tests_against_baseline() {
+        let check = check_baseline(
+            &baseline(),
+            vec![
+                sample_test("tests::a", "nightly_2", true, "a"),
+                sample_test("tests::b", "nightly_2", true, "changed"),
+                sample_test("tests::d", "nightly_2", true, "d"),
+            ],
+        );
+        assert_eq!(check.matches.len(), 1);
+        assert_eq!(check.differing[0].name, "tests::b");
+        assert_eq!(check.not_in_baseline[0].name, "tests::d");
+        assert_eq!(check.missing, vec!["tests::c".to_string()]);
+        assert!(!check.is_clean());
+    }
+
+    #[test]
+    fn baseline_round_trips() {
+        let path =
+            std::env::temp_dir().join(format!("referendum-baseline-{}.json", std::process::id()));
+        save_baseline(&path, &baseline()).unwrap();
+        let loaded = load_baseline(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.consensus.len(), 3);
+        assert_eq!(loaded.toolchains, baseline().toolchains);
+    }
+}
@@ -0,0 +1,415 @@
+use crate::build::configure;
+use crate::{referendum_dir, toolkit_command, toolkit_dir, ReferendumError, Result, RunOptions};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use string_builder::Builder;
+
+//benchmark name to nanoseconds per iteration, one entry per sample
+pub type Samples = BTreeMap<String, Vec<f64>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BenchVerdict {
+    Regression,
+    Improvement,
+    NoChange,
+}
+
+impl BenchVerdict {
+    fn label(&self) -> &'static str {
+        match self {
+            BenchVerdict::Regression => "REGRESSION",
+            BenchVerdict::Improvement => "improvement",
+            BenchVerdict::NoChange => "no significant change",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchComparison {
+    pub bench: String,
+    pub toolkit: String,
+    pub reference_mean: f64,
+    pub mean: f64,
+    pub p_value: f64,
+    pub verdict: BenchVerdict,
+}
+
+impl BenchComparison {
+    pub fn percent(&self) -> f64 {
+        (self.mean - self.reference_mean) / self.reference_mean * 100.0
+    }
+}
+
+#[derive(Debug)]
+pub struct BenchDifferential {
+    pub reference: String,
+    pub comparisons: Vec<BenchComparison>,
+}
+
+impl BenchDifferential {
+    pub fn regressions(&self) -> usize {
+        self.comparisons
+            .iter()
+            .filter(|comparison| comparison.verdict == BenchVerdict::Regression)
+            .count()
+    }
+}
+
+fn bench_error(e: impl std::fmt::Display) -> ReferendumError {
+    ReferendumError::BenchFailure(e.to_string())
+}
+
+//libtest prints "test name ... bench:   1,234 ns/iter (+/- 56)", newer releases with decimals
+pub fn parse_libtest_benches(output: &str) -> Vec<(String, f64)> {
+    let re = Regex::new(r"^test (\S+)\s+\.\.\. bench:\s+([\d,.]+) ns/iter").unwrap();
+    output
+        .lines()
+        .filter_map(|line| re.captures(line))
+        .filter_map(|caps| {
+            let ns = caps[2].replace(',', "").parse().ok()?;
+            Some((caps[1].to_string(), ns))
+        })
+        .collect()
+}
+
+//criterion's sample.json holds total times for increasing iteration counts
+pub fn parse_criterion_sample(sample: &str) -> Option<Vec<f64>> {
+    let sample: Value = serde_json::from_str(sample).ok()?;
+    let iters = sample["iters"].as_array()?;
+    let times = sample["times"].as_array()?;
+    iters
+        .iter()
+        .zip(times.iter())
+        .map(|(iters, time)| Some(time.as_f64()? / iters.as_f64()?))
+        .collect()
+}
+
+fn read_criterion(dir: &Path, samples: &mut Samples) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let new = path.join("new");
+        let sample = fs::read_to_string(new.join("sample.json")).ok();
+        let benchmark = fs::read_to_string(new.join("benchmark.json")).ok();
+        match (sample, benchmark) {
+            (Some(sample), Some(benchmark)) => {
+                let id = serde_json::from_str::<Value>(&benchmark)
+                    .ok()
+                    .and_then(|benchmark| benchmark["full_id"].as_str().map(|id| id.to_string()));
+                if let (Some(id), Some(times)) = (id, parse_criterion_sample(&sample)) {
+                    samples.entry(id).or_default().extend(times);
+                }
+            }
+            _ => read_criterion(&path, samples),
+        }
+    }
+}
+
+//every run adds one libtest sample per benchmark and criterion's full set of samples
+pub fn get_bench_samples(toolkit: &str, runs: usize) -> Result<Samples> {
+    let target = referendum_dir().join("bench").join(toolkit_dir(toolkit));
+    let options = RunOptions {
+        env: vec![(
+            "CARGO_TARGET_DIR".to_string(),
+            target.to_string_lossy().to_string(),
+        )],
+        ..Default::default()
+    };
+
+    let mut samples = Samples::new();
+    for _ in 0..runs {
+        let mut command = toolkit_command(toolkit, "cargo");
+        command.arg("bench");
+        configure(&mut command, &options);
+        let output = command.output().map_err(bench_error)?;
+        if !output.status.success() {
+            return Err(bench_error(format!(
+                "cargo bench failed with {}: {}",
+                toolkit,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        for (name, ns) in parse_libtest_benches(&String::from_utf8_lossy(&output.stdout)) {
+            samples.entry(name).or_default().push(ns);
+        }
+        read_criterion(&target.join("criterion"), &mut samples);
+    }
+    Ok(samples)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn variance(values: &[f64]) -> f64 {
+    let m = mean(values);
+    match values.len() {
+        0 | 1 => 0.0,
+        n => values.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (n - 1) as f64,
+    }
+}
+
+//Lanczos approximation, g = 7
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| {
+            sum + c / (x + i as f64 + 1.0)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+//continued fraction for the incomplete beta function, evaluated with Lentz's method
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        let numerator = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        for numerator in [
+            numerator,
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+    h
+}
+
+//regularized incomplete beta function I_x(a, b)
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    match x < (a + 1.0) / (a + b + 2.0) {
+        true => front * beta_continued_fraction(a, b, x) / a,
+        false => 1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b,
+    }
+}
+
+//two sided p-value of Student's t distribution
+pub fn t_test_p_value(t: f64, df: f64) -> f64 {
+    incomplete_beta(df / 2.0, 0.5, df / (df + t * t))
+}
+
+//a t-test needs a sample variance, so every benchmark has to be sampled at least twice
+pub const MIN_BENCH_RUNS: usize = 2;
+
+//Welch's t-test, which does not assume the toolkits have equal variance
+pub fn welch_t_test(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() < 2 || b.len() < 2 {
+        return 1.0;
+    }
+    let (va, vb) = (variance(a) / a.len() as f64, variance(b) / b.len() as f64);
+    let difference = mean(a) - mean(b);
+    if va + vb == 0.0 {
+        return if difference == 0.0 { 1.0 } else { 0.0 };
+    }
+    let t = difference / (va + vb).sqrt();
+    let df =
+        (va + vb).powi(2) / (va.powi(2) / (a.len() - 1) as f64 + vb.powi(2) / (b.len() - 1) as f64);
+    t_test_p_value(t, df)
+}
+
+//a difference has to be both significant and larger than the threshold percent to count
+pub fn compare_samples(
+    reference_samples: &Samples,
+    toolkit: &str,
+    samples: &Samples,
+    alpha: f64,
+    threshold: f64,
+) -> Vec<BenchComparison> {
+    samples
+        .iter()
+        .filter_map(|(bench, values)| {
+            let reference_values = reference_samples.get(bench)?;
+            let p_value = welch_t_test(reference_values, values);
+            let mut comparison = BenchComparison {
+                bench: bench.clone(),
+                toolkit: toolkit.to_string(),
+                reference_mean: mean(reference_values),
+                mean: mean(values),
+                p_value,
+                verdict: BenchVerdict::NoChange,
+            };
+            if p_value < alpha && comparison.percent().abs() > threshold {
+                comparison.verdict = match comparison.percent() > 0.0 {
+                    true => BenchVerdict::Regression,
+                    false => BenchVerdict::Improvement,
+                };
+            }
+            Some(comparison)
+        })
+        .collect()
+}
+
+pub fn bench_toolkits(
+    toolkits: &[&str],
+    runs: usize,
+    alpha: f64,
+    threshold: f64,
+) -> Result<BenchDifferential> {
+    if runs < MIN_BENCH_RUNS {
+        return Err(bench_error(format!(
+            "at least {} runs are needed to compare benchmarks, got {}",
+            MIN_BENCH_RUNS, runs
+        )));
+    }
+    let reference = toolkits[0];
+    let reference_samples = get_bench_samples(reference, runs)?;
+    if reference_samples.is_empty() {
+        return Err(bench_error(format!("no benchmarks ran with {}", reference)));
+    }
+    let mut comparisons = Vec::new();
+    for toolkit in toolkits[1..].iter() {
+        let samples = get_bench_samples(toolkit, runs)?;
+        comparisons.extend(compare_samples(
+            &reference_samples,
+            toolkit,
+            &samples,
+            alpha,
+            threshold,
+        ));
+    }
+    Ok(BenchDifferential {
+        reference: reference.to_string(),
+        comparisons,
+    })
+}
+
+pub fn get_bench_results(differential: &BenchDifferential) -> String {
+    let mut builder = Builder::default();
+    builder.append("Benchmark Results...\n");
+    builder.append(format!("compared against {}\n", differential.reference));
+    for comparison in differential.comparisons.iter() {
+        builder.append(format!(
+            "bench {} @ {} ... {}\n\t{:.1} ns/iter vs {:.1} ns/iter ({:+.1}%, p = {:.4})\n",
+            comparison.bench,
+            comparison.toolkit,
+            comparison.verdict.label(),
+            comparison.mean,
+            comparison.reference_mean,
+            comparison.percent(),
+            comparison.p_value
+        ));
+    }
+    builder.append(format!(
+        "{} regressions in {} comparisons\n",
+        differential.regressions(),
+        differential.comparisons.len()
+    ));
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn libtest_bench_lines_are_parsed() {
+        let output = "running 2 tests
+test benches::old_format ... bench:       1,234 ns/iter (+/- 56)
+test benches::new_format ... bench:          12.50 ns/iter (+/- 0.25)
+test tests::not_a_bench ... ok
+";
+        assert_eq!(
+            parse_libtest_benches(output),
+            vec![
+                ("benches::old_format".to_string(), 1234.0),
+                ("benches::new_format".to_string(), 12.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn criterion_samples_are_per_iteration() {
+        let sample = r#"{"sampling_mode":"Linear","iters":[1.0,2.0,4.0],"times":[10.0,22.0,40.0]}"#;
+        assert_eq!(
+            parse_criterion_sample(sample).unwrap(),
+            vec![10.0, 11.0, 10.0]
+        );
+    }
+
+    #[test]
+    fn t_distribution_p_values() {
+        //critical values of Student's t for a two sided 5% test
+        assert!((t_test_p_value(2.228, 10.0) - 0.05).abs() < 1e-3);
+        assert!((t_test_p_value(1.96, 1e6) - 0.05).abs() < 1e-3);
+        assert!((t_test_p_value(0.0, 5.0) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn slower_toolkit_is_a_regression() {
+        let samples = |values: &[f64]| -> Samples {
+            vec![("bench".to_string(), values.to_vec())]
+                .into_iter()
+                .collect()
+        };
+        let reference = samples(&[100.0, 101.0, 99.0, 100.5, 99.5]);
+        let slower = samples(&[120.0, 121.0, 119.0, 120.5, 119.5]);
+        let noisy = samples(&[90.0, 115.0, 95.0, 110.0, 101.0]);
+
+        let regression = compare_samples(&reference, "nightly", &slower, 0.05, 5.0);
+        assert_eq!(regression[0].verdict, BenchVerdict::Regression);
+        let noise = compare_samples(&reference, "nightly", &noisy, 0.05, 5.0);
+        assert_eq!(noise[0].verdict, BenchVerdict::NoChange);
+    }
+
+    #[test]
+    fn single_runs_are_rejected_before_benchmarking() {
+        assert!(matches!(
+            bench_toolkits(&["stable", "nightly"], 1, 0.05, 5.0),
+            Err(ReferendumError::BenchFailure(_))
+        ));
+    }
+}
@@ -0,0 +1,215 @@
+use crate::toolchain::{get_toolchains, Toolchain};
+use crate::{get_tests_with, ReferendumError, Result, RunOptions};
+use std::collections::BTreeMap;
+use string_builder::Builder;
+
+pub mod git;
+pub mod mir;
+pub mod opt;
+
+//test name to outcome hash for every test matched by the filter
+pub type Outcome = BTreeMap<String, u64>;
+
+//binary search for the first bad index, given that `good` is good and `bad` is bad
+pub fn find_first_bad<F>(mut good: usize, mut bad: usize, mut is_bad: F) -> Result<usize>
+where
+    F: FnMut(usize) -> Result<bool>,
+{
+    while bad - good > 1 {
+        let mid = good + (bad - good) / 2;
+        match is_bad(mid)? {
+            true => bad = mid,
+            false => good = mid,
+        }
+    }
+    Ok(bad)
+}
+
+pub fn get_outcome(toolkit: &str, options: &RunOptions) -> Result<Outcome> {
+    let tests = get_tests_with(vec![toolkit], options)?;
+    if tests.is_empty() {
+        return Err(ReferendumError::TestNotFound());
+    }
+    Ok(tests
+        .into_iter()
+        .map(|test| (test.name, test.hash))
+        .collect())
+}
+
+pub fn changed_tests(good: &Outcome, bad: &Outcome) -> Vec<String> {
+    let mut names: Vec<String> = good
+        .keys()
+        .chain(bad.keys())
+        .filter(|name| good.get(*name) != bad.get(*name))
+        .cloned()
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+pub fn sort_by_commit_date(mut toolchains: Vec<Toolchain>) -> Result<Vec<Toolchain>> {
+    if let Some(kit) = toolchains.iter().find(|kit| kit.commit_date.is_none()) {
+        return Err(ReferendumError::BisectFailure(format!(
+            "{} does not report a commit date",
+            kit.name
+        )));
+    }
+    //stable sort keeps the command line order for toolkits built on the same day
+    toolchains.sort_by(|a, b| a.commit_date.cmp(&b.commit_date));
+    Ok(toolchains)
+}
+
+#[derive(Debug)]
+pub struct ToolchainBisection {
+    pub last_good: Toolchain,
+    pub first_bad: Toolchain,
+    pub changed: Vec<String>,
+    pub steps: Vec<(String, bool)>,
+}
+
+pub fn bisect_toolchains(toolkits: &[&str], filter: &str) -> Result<ToolchainBisection> {
+    let toolchains = sort_by_commit_date(get_toolchains(toolkits)?)?;
+    if toolchains.len() < 2 {
+        return Err(ReferendumError::BisectFailure(
+            "at least two toolkits are needed to bisect".to_string(),
+        ));
+    }
+
+    let options = RunOptions {
+        filter: Some(filter.to_string()),
+        ..Default::default()
+    };
+    let last = toolchains.len() - 1;
+    let mut outcomes: Vec<Option<Outcome>> = vec![None; toolchains.len()];
+    let good = get_outcome(&toolchains[0].name, &options)?;
+    let bad = get_outcome(&toolchains[last].name, &options)?;
+    let mut steps = vec![
+        (toolchains[0].name.clone(), false),
+        (toolchains[last].name.clone(), good != bad),
+    ];
+    if good == bad {
+        return Err(ReferendumError::BisectFailure(format!(
+            "{} and {} agree on every test matching {}",
+            toolchains[0].name, toolchains[last].name, filter
+        )));
+    }
+    outcomes[last] = Some(bad);
+
+    let first_bad = find_first_bad(0, last, |i| {
+        let outcome = get_outcome(&toolchains[i].name, &options)?;
+        let is_bad = outcome != good;
+        steps.push((toolchains[i].name.clone(), is_bad));
+        outcomes[i] = Some(outcome);
+        Ok(is_bad)
+    })?;
+
+    //every index the search settles on as bad has been run
+    let first_bad_outcome = outcomes[first_bad].as_ref().unwrap();
+    Ok(ToolchainBisection {
+        last_good: toolchains[first_bad - 1].clone(),
+        first_bad: toolchains[first_bad].clone(),
+        changed: changed_tests(&good, first_bad_outcome),
+        steps,
+    })
+}
+
+fn describe_commit(toolchain: &Toolchain) -> String {
+    format!(
+        "{} ({}, commit {} from {})",
+        toolchain.name,
+        toolchain.release,
+        toolchain.commit_hash.as_deref().unwrap_or("unknown"),
+        toolchain.commit_date.as_deref().unwrap_or("unknown")
+    )
+}
+
+pub fn get_toolchain_bisection_results(bisection: &ToolchainBisection) -> String {
+    let mut builder = Builder::default();
+    builder.append("Toolchain Bisection Results...\n");
+    for (kit, is_bad) in bisection.steps.iter() {
+        builder.append(format!(
+            "\ttried {} ... {}\n",
+            kit,
+            if *is_bad { "bad" } else { "good" }
+        ));
+    }
+    builder.append(format!(
+        "last good: {}\n",
+        describe_commit(&bisection.last_good)
+    ));
+    builder.append(format!(
+        "first bad: {}\n",
+        describe_commit(&bisection.first_bad)
+    ));
+    if let (Some(good), Some(bad)) = (
+        &bisection.last_good.commit_hash,
+        &bisection.first_bad.commit_hash,
+    ) {
+        builder.append(format!(
+            "changes: https://github.com/rust-lang/rust/compare/{}...{}\n",
+            good, bad
+        ));
+    }
+    builder.append("changed tests:\n");
+    for name in bisection.changed.iter() {
+        builder.append(format!("\ttest {}\n", name));
+    }
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toolchain::parse_toolchain;
+
+    #[test]
+    fn first_bad_is_found() {
+        for first_bad in 1..10 {
+            let mut probes = 0;
+            let found = find_first_bad(0, 9, |i| {
+                probes += 1;
+                Ok(i >= first_bad)
+            })
+            .unwrap();
+            assert_eq!(found, first_bad);
+            assert!(probes <= 4);
+        }
+    }
+
+    #[test]
+    fn toolchains_sort_by_commit_date() {
+        let kit = |name: &str, date: &str| {
+            parse_toolchain(
+                name,
+                &format!("rustc 1.55.0-nightly\ncommit-date: {}\n", date),
+                "cargo 1.55.0",
+            )
+        };
+        let sorted = sort_by_commit_date(vec![
+            kit("c", "2021-06-03"),
+            kit("a", "2021-06-01"),
+            kit("b", "2021-06-02"),
+        ])
+        .unwrap();
+        let names: Vec<&str> = sorted.iter().map(|kit| kit.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn toolchains_without_dates_are_rejected() {
+        let kit = parse_toolchain("stage1", "rustc 1.56.0-dev\n", "cargo 1.56.0");
+        assert!(sort_by_commit_date(vec![kit]).is_err());
+    }
+
+    #[test]
+    fn changed_tests_include_added_and_removed() {
+        let good: Outcome = vec![("a".to_string(), 1), ("b".to_string(), 2)]
+            .into_iter()
+            .collect();
+        let bad: Outcome = vec![("a".to_string(), 1), ("c".to_string(), 3)]
+            .into_iter()
+            .collect();
+        assert_eq!(changed_tests(&good, &bad), vec!["b", "c"]);
+    }
+}
@@ -0,0 +1,223 @@
+use super::find_first_bad;
+use crate::{get_tests_with, referendum_dir, ReferendumError, Result, RunOptions, Test};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str;
+use string_builder::Builder;
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .map_err(|e| ReferendumError::BisectFailure(format!("failed to run git: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(ReferendumError::BisectFailure(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    match str::from_utf8(&output.stdout) {
+        Ok(v) => Ok(v.trim().to_string()),
+        Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
+    }
+}
+
+//removes the temporary worktree however the bisection ends
+struct Worktree {
+    repo: PathBuf,
+    path: PathBuf,
+}
+
+impl Worktree {
+    fn add(repo: &Path, rev: &str) -> Result<Worktree> {
+        let path = std::env::temp_dir().join(format!("referendum-bisect-{}", std::process::id()));
+        git(
+            repo,
+            &["worktree", "add", "--detach", &path.to_string_lossy(), rev],
+        )?;
+        Ok(Worktree {
+            repo: repo.to_path_buf(),
+            path,
+        })
+    }
+
+    fn checkout(&self, rev: &str) -> Result<()> {
+        git(&self.path, &["checkout", "--quiet", "--detach", rev]).map(|_| ())
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let _ = git(
+            &self.repo,
+            &[
+                "worktree",
+                "remove",
+                "--force",
+                &self.path.to_string_lossy(),
+            ],
+        );
+    }
+}
+
+//every toolkit ran every matching test and they all agree
+pub fn is_unanimous(tests: &[Test], toolkits: usize) -> bool {
+    let mut votes: BTreeMap<&str, Vec<u64>> = BTreeMap::new();
+    for test in tests {
+        votes.entry(&test.name).or_default().push(test.hash);
+    }
+    votes
+        .values()
+        .all(|hashes| hashes.len() == toolkits && hashes.iter().all(|hash| *hash == hashes[0]))
+}
+
+#[derive(Debug)]
+pub struct RevisionBisection {
+    pub last_good: String,
+    pub first_bad: String,
+    pub summary: String,
+    pub steps: Vec<(String, bool)>,
+}
+
+pub fn bisect_revisions(
+    toolkits: &[&str],
+    test: &str,
+    good: &str,
+    bad: &str,
+) -> Result<RevisionBisection> {
+    let cwd = std::env::current_dir().map_err(|e| ReferendumError::BisectFailure(e.to_string()))?;
+    let top = PathBuf::from(git(&cwd, &["rev-parse", "--show-toplevel"])?);
+    let prefix = git(&cwd, &["rev-parse", "--show-prefix"])?;
+
+    let good = git(
+        &top,
+        &["rev-parse", "--verify", &format!("{}^{{commit}}", good)],
+    )?;
+    let bad = git(
+        &top,
+        &["rev-parse", "--verify", &format!("{}^{{commit}}", bad)],
+    )?;
+    let mut revisions = vec![good.clone()];
+    revisions.extend(
+        git(
+            &top,
+            &[
+                "rev-list",
+                "--reverse",
+                "--first-parent",
+                &format!("{}..{}", good, bad),
+            ],
+        )?
+        .lines()
+        .map(|line| line.to_string()),
+    );
+    if revisions.len() < 2 {
+        return Err(ReferendumError::BisectFailure(format!(
+            "{} is not an ancestor of {}",
+            good, bad
+        )));
+    }
+
+    let worktree = Worktree::add(&top, &good)?;
+    //one target directory for every revision keeps dependency builds around
+    let options = RunOptions {
+        filter: Some(test.to_string()),
+        dir: Some(worktree.path.join(&prefix)),
+        env: vec![(
+            "CARGO_TARGET_DIR".to_string(),
+            cwd.join(referendum_dir())
+                .join("bisect-target")
+                .to_string_lossy()
+                .to_string(),
+        )],
+        ..Default::default()
+    };
+
+    let mut steps = Vec::new();
+    let mut probe = |rev: &str| -> Result<bool> {
+        worktree.checkout(rev)?;
+        let tests = get_tests_with(toolkits.to_vec(), &options)?;
+        if tests.is_empty() {
+            return Err(ReferendumError::BisectFailure(format!(
+                "no tests matching {} at {}",
+                test, rev
+            )));
+        }
+        let diverges = !is_unanimous(&tests, toolkits.len());
+        steps.push((rev.to_string(), diverges));
+        Ok(diverges)
+    };
+
+    let last = revisions.len() - 1;
+    if probe(&revisions[0])? {
+        return Err(ReferendumError::BisectFailure(format!(
+            "the referendum for {} is not unanimous at the good revision {}",
+            test, good
+        )));
+    }
+    if !probe(&revisions[last])? {
+        return Err(ReferendumError::BisectFailure(format!(
+            "the referendum for {} is unanimous at the bad revision {}",
+            test, bad
+        )));
+    }
+    let first_bad = find_first_bad(0, last, |i| probe(&revisions[i]))?;
+
+    Ok(RevisionBisection {
+        last_good: revisions[first_bad - 1].clone(),
+        first_bad: revisions[first_bad].clone(),
+        summary: git(
+            &top,
+            &["log", "-1", "--format=%h %s", &revisions[first_bad]],
+        )?,
+        steps,
+    })
+}
+
+pub fn get_revision_bisection_results(bisection: &RevisionBisection) -> String {
+    let mut builder = Builder::default();
+    builder.append("Revision Bisection Results...\n");
+    for (rev, diverges) in bisection.steps.iter() {
+        builder.append(format!(
+            "\ttried {} ... {}\n",
+            rev,
+            if *diverges { "divergent" } else { "unanimous" }
+        ));
+    }
+    builder.append(format!("last unanimous: {}\n", bisection.last_good));
+    builder.append(format!("first divergent: {}\n", bisection.first_bad));
+    builder.append(format!("\t{}\n", bisection.summary));
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample_test;
+
+    #[test]
+    fn unanimous_when_every_toolkit_agrees() {
+        let tests = vec![
+            sample_test("tests::a", "nightly_1", true, "same"),
+            sample_test("tests::a", "nightly_2", true, "same"),
+        ];
+        assert!(is_unanimous(&tests, 2));
+    }
+
+    #[test]
+    fn not_unanimous_on_dissent_or_missing_votes() {
+        let dissent = vec![
+            sample_test("tests::a", "nightly_1", true, "same"),
+            sample_test("tests::a", "nightly_2", true, "changed"),
+        ];
+        assert!(!is_unanimous(&dissent, 2));
+
+        let missing = vec![sample_test("tests::a", "nightly_1", true, "same")];
+        assert!(!is_unanimous(&missing, 2));
+    }
+}
@@ -0,0 +1,277 @@
+use crate::build::get_test_hash;
+use crate::{referendum_dir, toolkit_command, toolkit_dir, ReferendumError, Result, RunOptions};
+use std::fs;
+use string_builder::Builder;
+
+//full MIR optimizations with every LLVM pass disabled by the opt-bisect limit
+const LLVM_DISABLED: [&str; 4] = ["-C", "opt-level=3", "-C", "llvm-args=-opt-bisect-limit=0"];
+
+//small enough to compile in an instant, with loops, options and calls for the optimizing passes to work on
+const PROBE: &str = "pub fn probe(values: &[u32], extra: Option<u32>) -> u32 {
+    let mut sum = 0;
+    for value in values {
+        sum += *value;
+    }
+    sum + extra.unwrap_or(1)
+}
+";
+
+#[derive(Debug)]
+pub struct MirBisection {
+    pub level: u8,
+    pub passes: Vec<String>,
+    pub flags: String,
+}
+
+fn disable_args(level: Option<u8>, passes: &[String]) -> Vec<String> {
+    let mut args: Vec<String> = LLVM_DISABLED.iter().map(|arg| arg.to_string()).collect();
+    if let Some(level) = level {
+        args.push(format!("-Zmir-opt-level={}", level));
+    }
+    if !passes.is_empty() {
+        let disabled: Vec<String> = passes.iter().map(|pass| format!("-{}", pass)).collect();
+        args.push(format!("-Zmir-enable-passes={}", disabled.join(",")));
+    }
+    args
+}
+
+//dumps are named `crate.item.<phase>-<pass number>.<pass>.<before|after>.mir`, the optimizations run in phase 3-2
+pub fn parse_mir_dumps<'a>(files: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut passes: Vec<String> = files
+        .filter_map(|file| {
+            file.split_once(".3-2-")?
+                .1
+                .split_once('.')?
+                .1
+                .rsplit_once('.')
+        })
+        .filter_map(|(name, _)| name.strip_suffix(".after"))
+        //phase changes are dumped under lower case names
+        .filter(|name| name.starts_with(|c: char| c.is_ascii_uppercase()))
+        .map(|name| name.to_string())
+        .collect();
+    passes.sort();
+    passes.dedup();
+    passes
+}
+
+//the passes the toolkit runs at a MIR opt level, pass names change between rustc versions
+pub fn get_mir_passes(toolkit: &str, level: u8) -> Result<Vec<String>> {
+    let dir = referendum_dir()
+        .join("mir-bisect")
+        .join("passes")
+        .join(toolkit_dir(toolkit));
+    let dumps = dir.join("dump");
+    let failure = |e: std::io::Error| ReferendumError::BisectFailure(e.to_string());
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).map_err(failure)?;
+    fs::write(dir.join("probe.rs"), PROBE).map_err(failure)?;
+    let output = toolkit_command(toolkit, "rustc")
+        .args(LLVM_DISABLED)
+        .arg(format!("-Zmir-opt-level={}", level))
+        .arg("-Zdump-mir=all")
+        .arg(format!("-Zdump-mir-dir={}", dumps.to_string_lossy()))
+        .arg("--crate-type=lib")
+        .arg("--emit=metadata")
+        .arg("--out-dir")
+        .arg(&dir)
+        .arg(dir.join("probe.rs"))
+        .output()
+        .map_err(failure)?;
+    if !output.status.success() {
+        return Err(ReferendumError::BisectFailure(format!(
+            "{} could not list its MIR passes: {}",
+            toolkit,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let files: Vec<String> = fs::read_dir(&dumps)
+        .map_err(failure)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    Ok(parse_mir_dumps(files.iter().map(|file| file.as_str())))
+}
+
+//delta debugging: drop chunks of passes while disabling the rest still restores consensus
+pub fn minimize_passes<F>(mut passes: Vec<String>, mut restores: F) -> Result<Vec<String>>
+where
+    F: FnMut(&[String]) -> Result<bool>,
+{
+    let mut chunk = passes.len().div_ceil(2);
+    while chunk > 0 && passes.len() > 1 {
+        let mut start = 0;
+        let mut removed = false;
+        while start < passes.len() {
+            let end = (start + chunk).min(passes.len());
+            let candidate: Vec<String> = passes[..start]
+                .iter()
+                .chain(passes[end..].iter())
+                .cloned()
+                .collect();
+            if !candidate.is_empty() && restores(&candidate)? {
+                passes = candidate;
+                removed = true;
+            } else {
+                start = end;
+            }
+        }
+        if !removed || chunk == 1 {
+            chunk /= 2;
+        }
+    }
+    Ok(passes)
+}
+
+fn target_options(toolkit: &str) -> RunOptions {
+    RunOptions {
+        env: vec![(
+            "CARGO_TARGET_DIR".to_string(),
+            referendum_dir()
+                .join("mir-bisect")
+                .join(toolkit_dir(toolkit))
+                .to_string_lossy()
+                .to_string(),
+        )],
+        ..Default::default()
+    }
+}
+
+//`candidates` defaults to every pass the toolkit runs at the level where the dissent first appears
+pub fn bisect_mir_passes(
+    toolkit: &str,
+    reference: &str,
+    test: &str,
+    selection: &[String],
+    candidates: Option<&[String]>,
+) -> Result<MirBisection> {
+    let (consensus, _) =
+        get_test_hash(reference, test, selection, &[], &target_options(reference))?;
+    let options = target_options(toolkit);
+    let hash = |args: &[String]| get_test_hash(toolkit, test, selection, args, &options);
+
+    if hash(&disable_args(None, &[]))?.0 == consensus {
+        return Err(ReferendumError::BisectFailure(format!(
+            "{} agrees with {} once LLVM optimizations are disabled, try opt-bisect",
+            test, reference
+        )));
+    }
+
+    if hash(&disable_args(Some(0), &[]))?.0 != consensus {
+        return Err(ReferendumError::BisectFailure(format!(
+            "{} still dissents at -Zmir-opt-level=0, MIR optimizations are not responsible",
+            test
+        )));
+    }
+
+    let mut level = None;
+    for candidate in 1..=4 {
+        if hash(&disable_args(Some(candidate), &[]))?.0 != consensus {
+            level = Some(candidate);
+            break;
+        }
+    }
+    let level = level.ok_or_else(|| {
+        ReferendumError::BisectFailure(format!(
+            "{} agrees with {} at every -Zmir-opt-level from 1 to 4",
+            test, reference
+        ))
+    })?;
+
+    let candidates = match candidates {
+        Some(candidates) => candidates.to_vec(),
+        None => get_mir_passes(toolkit, level)?,
+    };
+    let restores = |passes: &[String]| Ok(hash(&disable_args(Some(level), passes))?.0 == consensus);
+    if !restores(&candidates)? {
+        return Err(ReferendumError::BisectFailure(
+            "disabling every candidate pass does not restore consensus, the responsible pass is not in the candidate list".to_string(),
+        ));
+    }
+
+    let passes = minimize_passes(candidates, restores)?;
+    Ok(MirBisection {
+        level,
+        flags: disable_args(Some(level), &passes).join(" "),
+        passes,
+    })
+}
+
+pub fn get_mir_bisection_results(bisection: &MirBisection) -> String {
+    let mut builder = Builder::default();
+    builder.append("MIR Pass Bisection Results...\n");
+    builder.append(format!(
+        "dissent first appears at -Zmir-opt-level={}\n",
+        bisection.level
+    ));
+    builder.append("disabling these passes restores consensus:\n");
+    for pass in bisection.passes.iter() {
+        builder.append(format!("\t{}\n", pass));
+    }
+    builder.append(format!(
+        "reproduce with rustc flags:\n\t{}\n",
+        bisection.flags
+    ));
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(passes: &[&str]) -> Vec<String> {
+        passes.iter().map(|pass| pass.to_string()).collect()
+    }
+
+    #[test]
+    fn minimize_finds_single_pass() {
+        let passes = names(&[
+            "CopyProp",
+            "GVN",
+            "Inline",
+            "JumpThreading",
+            "SimplifyCfg-final",
+        ]);
+        let minimal = minimize_passes(passes, |candidate| {
+            Ok(candidate.contains(&"GVN".to_string()))
+        })
+        .unwrap();
+        assert_eq!(minimal, vec!["GVN"]);
+    }
+
+    #[test]
+    fn minimize_keeps_interacting_passes() {
+        let passes = names(&["A", "B", "C", "D", "E", "F"]);
+        let minimal = minimize_passes(passes, |candidate| {
+            Ok(candidate.contains(&"B".to_string()) && candidate.contains(&"E".to_string()))
+        })
+        .unwrap();
+        assert_eq!(minimal, vec!["B", "E"]);
+    }
+
+    #[test]
+    fn disable_args_negate_passes() {
+        assert_eq!(
+            disable_args(Some(2), &names(&["GVN", "Inline"])).join(" "),
+            "-C opt-level=3 -C llvm-args=-opt-bisect-limit=0 -Zmir-opt-level=2 -Zmir-enable-passes=-GVN,-Inline"
+        );
+    }
+
+    #[test]
+    fn optimization_passes_come_from_dumps() {
+        let files = [
+            "probe.probe.-------.nll.0.mir",
+            "probe.probe.1-1-006.SimplifyCfg-initial.after.mir",
+            "probe.probe.3-1-000.runtime.after.mir",
+            "probe.probe.3-2-004.GVN.before.mir",
+            "probe.probe.3-2-004.GVN.after.mir",
+            "probe.probe.3-2-012.SimplifyCfg-final.after.mir",
+            "probe.probe.3-2-013.runtime-post-cleanup.after.mir",
+            "probe.{impl#0}-fmt.3-2-001.Inline.after.mir",
+        ];
+        assert_eq!(
+            parse_mir_dumps(files.iter().copied()),
+            vec!["GVN", "Inline", "SimplifyCfg-final"]
+        );
+    }
+}
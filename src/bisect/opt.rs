@@ -0,0 +1,147 @@
+use super::find_first_bad;
+use crate::build::{clean_package, get_test_hash};
+use crate::{command_line, referendum_dir, toolkit_command, ReferendumError, Result, RunOptions};
+use regex::Regex;
+use string_builder::Builder;
+
+//LLVM only numbers passes deterministically with a single codegen unit
+const OPTIMIZED: [&str; 4] = ["-C", "opt-level=3", "-C", "codegen-units=1"];
+
+#[derive(Debug)]
+pub struct OptBisection {
+    pub pass_count: usize,
+    pub limit: usize,
+    pub pass: String,
+    pub reproduce: String,
+}
+
+pub fn parse_bisect_passes(stderr: &str) -> Vec<(usize, String)> {
+    let re = Regex::new(r"BISECT: running pass \((\d+)\) (.+)").unwrap();
+    stderr
+        .lines()
+        .filter_map(|line| re.captures(line))
+        .filter_map(|caps| Some((caps[1].parse().ok()?, caps[2].trim().to_string())))
+        .collect()
+}
+
+fn limit_args(limit: usize) -> Vec<String> {
+    let mut args: Vec<String> = OPTIMIZED.iter().map(|arg| arg.to_string()).collect();
+    args.push("-C".to_string());
+    args.push(format!("llvm-args=-opt-bisect-limit={}", limit));
+    args
+}
+
+pub fn bisect_opt_passes(toolkit: &str, test: &str, selection: &[String]) -> Result<OptBisection> {
+    let options = RunOptions {
+        env: vec![(
+            "CARGO_TARGET_DIR".to_string(),
+            referendum_dir()
+                .join("opt-bisect")
+                .to_string_lossy()
+                .to_string(),
+        )],
+        ..Default::default()
+    };
+    let hash = |args: &[String]| get_test_hash(toolkit, test, selection, args, &options);
+    let strings = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+    let (reference, _) = hash(&strings(&["-C", "opt-level=0"]))?;
+    let (optimized, _) = hash(&strings(&OPTIMIZED))?;
+    if reference == optimized {
+        return Err(ReferendumError::BisectFailure(format!(
+            "{} agrees at opt-level 0 and 3 on {}",
+            test, toolkit
+        )));
+    }
+
+    //a clean build makes LLVM list every pass it would run
+    clean_package(toolkit, &options)?;
+    let (_, stderr) = hash(&limit_args(i32::MAX as usize))?;
+    let passes = parse_bisect_passes(&stderr);
+    let pass_count = match passes.iter().map(|(index, _)| *index).max() {
+        Some(count) => count,
+        None => {
+            return Err(ReferendumError::BisectFailure(
+                "LLVM did not report any passes, is -opt-bisect-limit supported?".to_string(),
+            ))
+        }
+    };
+
+    if hash(&limit_args(0))?.0 != reference {
+        return Err(ReferendumError::BisectFailure(format!(
+            "{} diverges with every LLVM pass disabled, the change happens before LLVM",
+            test
+        )));
+    }
+    if hash(&limit_args(pass_count))?.0 == reference {
+        return Err(ReferendumError::BisectFailure(format!(
+            "{} does not diverge when every LLVM pass is run under -opt-bisect-limit",
+            test
+        )));
+    }
+
+    let limit = find_first_bad(0, pass_count, |limit| {
+        Ok(hash(&limit_args(limit))?.0 != reference)
+    })?;
+    let pass = passes
+        .iter()
+        .find(|(index, _)| *index == limit)
+        .map(|(_, pass)| pass.clone())
+        .unwrap_or_else(|| "unknown pass".to_string());
+
+    let mut reproduce = toolkit_command(toolkit, "cargo");
+    reproduce
+        .arg("rustc")
+        .arg("--profile")
+        .arg("test")
+        .args(selection)
+        .arg("--")
+        .args(limit_args(limit));
+    Ok(OptBisection {
+        pass_count,
+        limit,
+        pass,
+        reproduce: command_line(&reproduce),
+    })
+}
+
+pub fn get_opt_bisection_results(bisection: &OptBisection) -> String {
+    let mut builder = Builder::default();
+    builder.append("LLVM Pass Bisection Results...\n");
+    builder.append(format!(
+        "output diverges at pass {} of {}\n",
+        bisection.limit, bisection.pass_count
+    ));
+    builder.append(format!("\t{}\n", bisection.pass));
+    builder.append(format!("reproduce with:\n\t{}\n", bisection.reproduce));
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bisect_passes_are_parsed() {
+        let stderr = "   Compiling referendum v0.1.0
+BISECT: running pass (1) SimplifyCFGPass on tests::test_1
+BISECT: running pass (2) SROAPass on tests::test_1
+BISECT: NOT running pass (3) InstCombinePass on tests::test_1
+";
+        assert_eq!(
+            parse_bisect_passes(stderr),
+            vec![
+                (1, "SimplifyCFGPass on tests::test_1".to_string()),
+                (2, "SROAPass on tests::test_1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn limit_args_keep_optimizations_fixed() {
+        assert_eq!(
+            limit_args(12).join(" "),
+            "-C opt-level=3 -C codegen-units=1 -C llvm-args=-opt-bisect-limit=12"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{parse_tests, toolkit_command, ReferendumError, Result, RunOptions, Test};
+use serde_json::Value;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str;
+
+#[derive(Debug)]
+pub struct TestBuild {
+    pub executables: Vec<PathBuf>,
+    pub stderr: String,
+}
+
+fn build_error(e: impl std::fmt::Display) -> ReferendumError {
+    ReferendumError::BuildFailure(e.to_string())
+}
+
+//test executables from cargo's --message-format=json output
+pub fn parse_test_executables(messages: &str) -> Vec<PathBuf> {
+    messages
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|message| message["reason"] == "compiler-artifact")
+        .filter(|message| message["profile"]["test"] == true)
+        .filter_map(|message| message["executable"].as_str().map(PathBuf::from))
+        .collect()
+}
+
+pub(crate) fn configure(command: &mut Command, options: &RunOptions) {
+    if let Some(dir) = &options.dir {
+        command.current_dir(dir);
+    }
+    command.envs(options.env.iter().map(|(key, value)| (key, value)));
+}
+
+//flags are added to the ones already set, by earlier options or the user's environment, rather than replacing them
+pub(crate) fn append_flags(options: &RunOptions, key: &str, flags: &str) -> (String, String) {
+    let mut value = match options.env.iter().rev().find(|(name, _)| name == key) {
+        Some((_, value)) => value.clone(),
+        None => env::var(key).unwrap_or_default(),
+    };
+    if !value.is_empty() {
+        value.push(' ');
+    }
+    value.push_str(flags);
+    (key.to_string(), value)
+}
+
+//rebuilds a single test target, passing extra arguments to rustc for that target only
+pub fn build_test_target(
+    toolkit: &str,
+    selection: &[String],
+    rustc_args: &[String],
+    options: &RunOptions,
+) -> Result<TestBuild> {
+    let mut command = toolkit_command(toolkit, "cargo");
+    command
+        .arg("rustc")
+        .arg("--profile")
+        .arg("test")
+        .arg("--message-format=json")
+        .args(selection)
+        .arg("--")
+        .args(rustc_args);
+    configure(&mut command, options);
+    let output = command.output().map_err(build_error)?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    if !output.status.success() {
+        return Err(build_error(format!(
+            "cargo rustc with {} failed",
+            rustc_args.join(" ")
+        )));
+    }
+
+    Ok(TestBuild {
+        executables: parse_test_executables(&stdout),
+        stderr,
+    })
+}
+
+pub fn run_test_executable(
+    toolkit: &str,
+    executable: &Path,
+    test: &str,
+    options: &RunOptions,
+) -> Result<Vec<Test>> {
+    let mut command = Command::new(executable);
+    command
+        .arg(test)
+        .arg("--exact")
+        .arg("--test-threads=1")
+        .arg("--show-output");
+    configure(&mut command, options);
+    let output = command.output().map_err(build_error)?;
+
+    let stdout = match str::from_utf8(&output.stdout) {
+        Ok(v) => v.to_string(),
+        Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
+    };
+    if !output.status.success() && !stdout.contains("test result:") {
+        return Err(ReferendumError::TestRunFailure());
+    }
+    parse_tests(toolkit, &stdout)
+}
+
+//the outcome hash of a single test built with the given rustc arguments
+pub fn get_test_hash(
+    toolkit: &str,
+    test: &str,
+    selection: &[String],
+    rustc_args: &[String],
+    options: &RunOptions,
+) -> Result<(u64, String)> {
+    let build = build_test_target(toolkit, selection, rustc_args, options)?;
+    for executable in build.executables.iter() {
+        let tests = run_test_executable(toolkit, executable, test, options)?;
+        if let Some(found) = tests.into_iter().find(|found| found.name == test) {
+            return Ok((found.hash, build.stderr));
+        }
+    }
+    Err(ReferendumError::TestNotFound())
+}
+
+//forces the package itself to be rebuilt without touching its dependencies
+pub fn clean_package(toolkit: &str, options: &RunOptions) -> Result<()> {
+    let mut pkgid = toolkit_command(toolkit, "cargo");
+    pkgid.arg("pkgid");
+    configure(&mut pkgid, options);
+    let output = pkgid.output().map_err(build_error)?;
+    if !output.status.success() {
+        return Err(build_error("cargo pkgid failed"));
+    }
+    let spec = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    let mut clean = toolkit_command(toolkit, "cargo");
+    clean.arg("clean").arg("-p").arg(&spec);
+    configure(&mut clean, options);
+    match clean.output().map_err(build_error)?.status.success() {
+        true => Ok(()),
+        false => Err(build_error(format!("cargo clean -p {} failed", spec))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn executables_are_read_from_artifacts() {
+        let messages = r#"{"reason":"compiler-artifact","profile":{"test":false},"executable":null}
+{"reason":"compiler-artifact","profile":{"test":true},"executable":"/work/target/debug/deps/crate-0123"}
+{"reason":"build-finished","success":true}"#;
+        assert_eq!(
+            parse_test_executables(messages),
+            vec![PathBuf::from("/work/target/debug/deps/crate-0123")]
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use crate::build::configure;
+use crate::{toolkit_command, ReferendumError, Result, RunOptions};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use string_builder::Builder;
+
+//diagnostics are identified by code, level and primary span, wording changes between releases
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: u64,
+    pub column: u64,
+    pub level: String,
+    pub code: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "{}[{}]", self.level, code)?,
+            None => write!(f, "{}", self.level)?,
+        }
+        write!(f, " {}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+//diagnostic to the message the toolkit reported with it
+pub type Diagnostics = BTreeMap<Diagnostic, String>;
+
+#[derive(Debug)]
+pub struct DiagnosticDissent {
+    pub toolkit: String,
+    pub new: Vec<(Diagnostic, String)>,
+    pub vanished: Vec<(Diagnostic, String)>,
+}
+
+#[derive(Debug)]
+pub struct DiagnosticVote {
+    pub consensus: Option<Diagnostics>,
+    pub dissents: Vec<DiagnosticDissent>,
+    pub no_consensus: Vec<(String, Diagnostics)>,
+}
+
+pub fn parse_diagnostics(messages: &str) -> Diagnostics {
+    let mut diagnostics = Diagnostics::new();
+    for message in messages
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|message| message["reason"] == "compiler-message")
+    {
+        let message = &message["message"];
+        //summaries such as "aborting due to 2 previous errors" have no span
+        let span = match message["spans"]
+            .as_array()
+            .and_then(|spans| spans.iter().find(|span| span["is_primary"] == true))
+        {
+            Some(span) => span,
+            None => continue,
+        };
+        let diagnostic = Diagnostic {
+            file: span["file_name"].as_str().unwrap_or("").to_string(),
+            line: span["line_start"].as_u64().unwrap_or(0),
+            column: span["column_start"].as_u64().unwrap_or(0),
+            level: message["level"].as_str().unwrap_or("").to_string(),
+            code: message["code"]["code"]
+                .as_str()
+                .map(|code| code.to_string()),
+        };
+        let text = message["message"].as_str().unwrap_or("").to_string();
+        diagnostics.entry(diagnostic).or_insert(text);
+    }
+    diagnostics
+}
+
+//cargo replays the diagnostics of fresh units, so this is cheap right after cargo test
+pub fn get_diagnostics_with(toolkit: &str, options: &RunOptions) -> Result<Diagnostics> {
+    let mut command = toolkit_command(toolkit, "cargo");
+    command
+        .arg("test")
+        .arg("--no-run")
+        .arg("--message-format=json");
+    configure(&mut command, options);
+    let output = command
+        .output()
+        .map_err(|e| ReferendumError::BuildFailure(e.to_string()))?;
+    check_diagnostics_build(
+        output.status.success(),
+        &String::from_utf8_lossy(&output.stdout),
+        &String::from_utf8_lossy(&output.stderr),
+    )
+}
+
+//a build that fails still votes with its errors, unless cargo never got as far as the compiler
+fn check_diagnostics_build(success: bool, stdout: &str, stderr: &str) -> Result<Diagnostics> {
+    let compiled = stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .any(|message| message["reason"] == "compiler-message");
+    match success || compiled {
+        true => Ok(parse_diagnostics(stdout)),
+        false => Err(ReferendumError::BuildFailure(stderr.trim().to_string())),
+    }
+}
+
+pub fn get_diagnostics(toolkits: &[&str]) -> Result<Vec<(String, Diagnostics)>> {
+    toolkits
+        .iter()
+        .map(|kit| {
+            Ok((
+                kit.to_string(),
+                get_diagnostics_with(kit, &RunOptions::default())?,
+            ))
+        })
+        .collect()
+}
+
+fn keys(diagnostics: &Diagnostics) -> Vec<&Diagnostic> {
+    diagnostics.keys().collect()
+}
+
+fn missing_from(from: &Diagnostics, other: &Diagnostics) -> Vec<(Diagnostic, String)> {
+    from.iter()
+        .filter(|(diagnostic, _)| !other.contains_key(diagnostic))
+        .map(|(diagnostic, text)| (diagnostic.clone(), text.clone()))
+        .collect()
+}
+
+//each toolkit votes with its whole set of diagnostics, ties go to the earliest toolkit
+pub fn vote_diagnostics(votes: Vec<(String, Diagnostics)>) -> DiagnosticVote {
+    let mut counts: Vec<(usize, usize)> = Vec::new();
+    for (i, (_, diagnostics)) in votes.iter().enumerate() {
+        match counts
+            .iter_mut()
+            .find(|(first, _)| keys(&votes[*first].1) == keys(diagnostics))
+        {
+            Some((_, count)) => *count += 1,
+            None => counts.push((i, 1)),
+        }
+    }
+    let winner = counts
+        .iter()
+        .fold(None, |max: Option<&(usize, usize)>, entry| match max {
+            Some(max) if max.1 >= entry.1 => Some(max),
+            _ => Some(entry),
+        });
+
+    match winner {
+        Some((first, count)) if *count > 1 || votes.len() == 1 => {
+            let consensus = votes[*first].1.clone();
+            let dissents = votes
+                .iter()
+                .filter(|(_, diagnostics)| keys(diagnostics) != keys(&consensus))
+                .map(|(toolkit, diagnostics)| DiagnosticDissent {
+                    toolkit: toolkit.clone(),
+                    new: missing_from(diagnostics, &consensus),
+                    vanished: missing_from(&consensus, diagnostics),
+                })
+                .collect();
+            DiagnosticVote {
+                consensus: Some(consensus),
+                dissents,
+                no_consensus: Vec::new(),
+            }
+        }
+        _ => DiagnosticVote {
+            consensus: None,
+            dissents: Vec::new(),
+            no_consensus: votes,
+        },
+    }
+}
+
+fn generate_diagnostic_output(sign: &str, diagnostic: &Diagnostic, text: &str) -> String {
+    format!("\t{} {} {}\n", sign, diagnostic, text)
+}
+
+pub fn get_diagnostic_results(vote: &DiagnosticVote) -> String {
+    let mut builder = Builder::default();
+    builder.append("Diagnostic Results...\n");
+    match &vote.consensus {
+        Some(consensus) => {
+            builder.append(format!("{} consensus diagnostics\n", consensus.len()));
+            for dissent in vote.dissents.iter() {
+                builder.append(format!(
+                    "diagnostics @ {}: {} new, {} vanished\n",
+                    dissent.toolkit,
+                    dissent.new.len(),
+                    dissent.vanished.len()
+                ));
+                for (diagnostic, text) in dissent.new.iter() {
+                    builder.append(generate_diagnostic_output("+", diagnostic, text));
+                }
+                for (diagnostic, text) in dissent.vanished.iter() {
+                    builder.append(generate_diagnostic_output("-", diagnostic, text));
+                }
+            }
+        }
+        None => {
+            builder.append("no consensus on diagnostics\n");
+            for (toolkit, diagnostics) in vote.no_consensus.iter() {
+                builder.append(format!("diagnostics @ {}:\n", toolkit));
+                for (diagnostic, text) in diagnostics.iter() {
+                    builder.append(generate_diagnostic_output(" ", diagnostic, text));
+                }
+            }
+        }
+    }
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warning(line: u64, code: &str) -> Diagnostic {
+        Diagnostic {
+            file: "src/lib.rs".to_string(),
+            line,
+            column: 9,
+            level: "warning".to_string(),
+            code: Some(code.to_string()),
+        }
+    }
+
+    fn set(diagnostics: &[Diagnostic]) -> Diagnostics {
+        diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.clone(), "message".to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn diagnostics_are_parsed_from_primary_spans() {
+        let messages = r#"{"reason":"compiler-message","message":{"message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"src/lib.rs","line_start":3,"column_start":9,"is_primary":true}]}}
+{"reason":"compiler-message","message":{"message":"1 warning emitted","code":null,"level":"warning","spans":[]}}
+{"reason":"compiler-artifact","profile":{"test":true}}"#;
+        let diagnostics = parse_diagnostics(messages);
+        assert_eq!(diagnostics.len(), 1);
+        let (diagnostic, text) = diagnostics.iter().next().unwrap();
+        assert_eq!(
+            diagnostic.to_string(),
+            "warning[unused_variables] src/lib.rs:3:9"
+        );
+        assert_eq!(text, "unused variable: `x`");
+    }
+
+    #[test]
+    fn failed_builds_vote_only_with_compiler_errors() {
+        let errors = r#"{"reason":"compiler-message","message":{"message":"mismatched types","code":{"code":"E0308","explanation":null},"level":"error","spans":[{"file_name":"src/lib.rs","line_start":5,"column_start":13,"is_primary":true}]}}"#;
+        let diagnostics =
+            check_diagnostics_build(false, errors, "error: could not compile").unwrap();
+        assert_eq!(diagnostics.len(), 1);
+
+        assert!(matches!(
+            check_diagnostics_build(false, "", "error: failed to parse manifest\n"),
+            Err(ReferendumError::BuildFailure(stderr)) if stderr == "error: failed to parse manifest"
+        ));
+    }
+
+    #[test]
+    fn new_and_vanished_diagnostics_are_reported() {
+        let consensus = set(&[warning(3, "unused_variables"), warning(7, "dead_code")]);
+        let dissent = set(&[warning(3, "unused_variables"), warning(9, "unused_mut")]);
+        let vote = vote_diagnostics(vec![
+            ("stable".to_string(), consensus.clone()),
+            ("beta".to_string(), consensus),
+            ("nightly".to_string(), dissent),
+        ]);
+        assert_eq!(vote.dissents.len(), 1);
+        assert_eq!(vote.dissents[0].toolkit, "nightly");
+        assert_eq!(vote.dissents[0].new[0].0, warning(9, "unused_mut"));
+        assert_eq!(vote.dissents[0].vanished[0].0, warning(7, "dead_code"));
+    }
+
+    #[test]
+    fn messages_do_not_split_the_vote() {
+        let mut reworded = set(&[warning(3, "unused_variables")]);
+        for text in reworded.values_mut() {
+            *text = "reworded".to_string();
+        }
+        let vote = vote_diagnostics(vec![
+            ("stable".to_string(), set(&[warning(3, "unused_variables")])),
+            ("nightly".to_string(), reworded),
+        ]);
+        assert!(vote.consensus.is_some());
+        assert!(vote.dissents.is_empty());
+    }
+
+    #[test]
+    fn every_set_differing_has_no_consensus() {
+        let vote = vote_diagnostics(vec![
+            ("stable".to_string(), set(&[warning(3, "unused_variables")])),
+            ("nightly".to_string(), set(&[])),
+        ]);
+        assert!(vote.consensus.is_none());
+        assert_eq!(vote.no_consensus.len(), 2);
+    }
+}
@@ -0,0 +1,121 @@
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Equal(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+//plain LCS line diff, test outputs are small enough that O(n * m) is fine
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = match old[i] == new[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Equal(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|line| DiffLine::Removed(line)));
+    lines.extend(new[j..].iter().map(|line| DiffLine::Added(line)));
+    lines
+}
+
+//pairs removed and added runs so they can be shown next to each other
+pub fn side_by_side<'a>(lines: &[DiffLine<'a>]) -> Vec<(Option<&'a str>, Option<&'a str>)> {
+    let mut rows = Vec::new();
+    let mut removed: Vec<&str> = Vec::new();
+    let mut added: Vec<&str> = Vec::new();
+
+    let flush = |rows: &mut Vec<(Option<&'a str>, Option<&'a str>)>,
+                 removed: &mut Vec<&'a str>,
+                 added: &mut Vec<&'a str>| {
+        for k in 0..removed.len().max(added.len()) {
+            rows.push((removed.get(k).copied(), added.get(k).copied()));
+        }
+        removed.clear();
+        added.clear();
+    };
+
+    for line in lines {
+        match line {
+            DiffLine::Equal(text) => {
+                flush(&mut rows, &mut removed, &mut added);
+                rows.push((Some(*text), Some(*text)));
+            }
+            DiffLine::Removed(text) => removed.push(text),
+            DiffLine::Added(text) => added.push(text),
+        }
+    }
+    flush(&mut rows, &mut removed, &mut added);
+    rows
+}
+
+pub fn unified_diff(old: &str, new: &str) -> String {
+    diff_lines(old, new)
+        .iter()
+        .map(|line| match line {
+            DiffLine::Equal(text) => format!(" {}\n", text),
+            DiffLine::Removed(text) => format!("-{}\n", text),
+            DiffLine::Added(text) => format!("+{}\n", text),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_marks_changed_lines() {
+        let lines = diff_lines("a\nb\nc", "a\nx\nc\nd");
+        assert_eq!(
+            lines,
+            vec![
+                DiffLine::Equal("a"),
+                DiffLine::Removed("b"),
+                DiffLine::Added("x"),
+                DiffLine::Equal("c"),
+                DiffLine::Added("d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn side_by_side_pairs_changes() {
+        let lines = diff_lines("a\nb\nc", "a\nx\nc\nd");
+        assert_eq!(
+            side_by_side(&lines),
+            vec![
+                (Some("a"), Some("a")),
+                (Some("b"), Some("x")),
+                (Some("c"), Some("c")),
+                (None, Some("d")),
+            ]
+        );
+    }
+
+    #[test]
+    fn unified_diff_prefixes() {
+        assert_eq!(unified_diff("a\nb", "a\nc"), " a\n-b\n+c\n");
+    }
+}
@@ -0,0 +1,247 @@
+use crate::report::{generate_verdict_matrix, is_problem, toolchain_names};
+use crate::toolchain::Toolchain;
+use crate::{referendum_dir, ReferendumError, Result, VoteResult};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use string_builder::Builder;
+
+//a run holds every Test record through its VoteResult
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Run {
+    pub timestamp: u64,
+    pub toolchains: Vec<Toolchain>,
+    pub votes: VoteResult,
+}
+
+impl Run {
+    pub fn new(toolchains: Vec<Toolchain>, votes: VoteResult) -> Run {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+        Run {
+            timestamp,
+            toolchains,
+            votes,
+        }
+    }
+}
+
+pub fn history_path() -> PathBuf {
+    referendum_dir().join("history.jsonl")
+}
+
+fn history_error(e: impl std::fmt::Display) -> ReferendumError {
+    ReferendumError::HistoryFailure(e.to_string())
+}
+
+pub fn record_run(path: &Path, run: &Run) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(history_error)?;
+    }
+    let line = serde_json::to_string(run).map_err(history_error)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(history_error)?;
+    //a run cut short while it was appended must not swallow this one
+    let truncated = fs::read(path)
+        .map(|contents| contents.last().is_some_and(|byte| *byte != b'\n'))
+        .unwrap_or(false);
+    if truncated {
+        writeln!(file).map_err(history_error)?;
+    }
+    writeln!(file, "{}", line).map_err(history_error)
+}
+
+//the readable runs, and the line numbers of any that are not, e.g. a run cut short while it was appended
+pub fn load_runs(path: &Path) -> Result<(Vec<Run>, Vec<usize>)> {
+    let contents = match fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), Vec::new())),
+        Err(e) => return Err(history_error(e)),
+    };
+    let mut runs = Vec::new();
+    let mut unreadable = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(run) => runs.push(run),
+            Err(_) => unreadable.push(i + 1),
+        }
+    }
+    Ok((runs, unreadable))
+}
+
+//civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        (seconds % 3600) / 60
+    )
+}
+
+pub fn get_history_listing(runs: &[Run]) -> String {
+    let mut builder = Builder::default();
+    builder.append("Referendum History...\n");
+    for (i, run) in runs.iter().enumerate() {
+        let matrix = generate_verdict_matrix(&run.votes, &toolchain_names(&run.toolchains));
+        let problems = matrix.iter().filter(|(_, row)| is_problem(row)).count();
+        builder.append(format!(
+            "#{} {}  {} tests, {} divergent  [{}]\n",
+            i + 1,
+            format_timestamp(run.timestamp),
+            matrix.len(),
+            problems,
+            run.toolchains
+                .iter()
+                .map(|kit| format!("{} ({})", kit.name, kit.release))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+    }
+    builder.string().unwrap()
+}
+
+pub fn get_test_history(runs: &[Run], test_name: &str) -> String {
+    let mut builder = Builder::default();
+    builder.append(format!("History of {}...\n", test_name));
+    for (i, run) in runs.iter().enumerate() {
+        let toolkits = toolchain_names(&run.toolchains);
+        let matrix = generate_verdict_matrix(&run.votes, &toolkits);
+        let row = match matrix.iter().find(|(name, _)| name == test_name) {
+            Some((_, row)) => row,
+            None => continue,
+        };
+        let verdicts: Vec<String> = toolkits
+            .iter()
+            .zip(row.iter())
+            .map(|(kit, verdict)| format!("{} {}", kit, verdict.label()))
+            .collect();
+        builder.append(format!(
+            "#{} {}  {}\n",
+            i + 1,
+            format_timestamp(run.timestamp),
+            verdicts.join(", ")
+        ));
+    }
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toolchain::sample_toolchain;
+    use crate::{sample_test, vote};
+
+    fn sample_run(timestamp: u64, dissent: bool) -> Run {
+        let votes = vote(vec![
+            sample_test("tests::a", "nightly_1", true, "same"),
+            sample_test("tests::a", "nightly_2", true, "same"),
+            sample_test(
+                "tests::a",
+                "nightly_3",
+                true,
+                if dissent { "changed" } else { "same" },
+            ),
+        ])
+        .unwrap();
+        Run {
+            timestamp,
+            toolchains: vec![
+                sample_toolchain("nightly_1"),
+                sample_toolchain("nightly_2"),
+                sample_toolchain("nightly_3"),
+            ],
+            votes,
+        }
+    }
+
+    #[test]
+    fn record_and_load_runs() {
+        let path =
+            std::env::temp_dir().join(format!("referendum-history-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        record_run(&path, &sample_run(0, false)).unwrap();
+        record_run(&path, &sample_run(86400, true)).unwrap();
+
+        let (runs, unreadable) = load_runs(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert!(unreadable.is_empty());
+        assert_eq!(runs[1].votes.non_matches.len(), 1);
+        assert_eq!(runs[1].toolchains[0], sample_toolchain("nightly_1"));
+    }
+
+    #[test]
+    fn truncated_runs_are_skipped() {
+        let path = std::env::temp_dir().join(format!(
+            "referendum-history-truncated-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        record_run(&path, &sample_run(0, false)).unwrap();
+        let line = serde_json::to_string(&sample_run(86400, true)).unwrap();
+        fs::write(
+            &path,
+            format!(
+                "{}{}",
+                fs::read_to_string(&path).unwrap(),
+                &line[..line.len() / 2]
+            ),
+        )
+        .unwrap();
+
+        record_run(&path, &sample_run(172800, false)).unwrap();
+
+        let (runs, unreadable) = load_runs(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1].timestamp, 172800);
+        assert_eq!(unreadable, vec![2]);
+    }
+
+    #[test]
+    fn missing_history_is_empty() {
+        let (runs, _) = load_runs(Path::new("/nonexistent/referendum/history.jsonl")).unwrap();
+        assert!(runs.is_empty());
+    }
+
+    #[test]
+    fn timestamps_are_formatted_as_utc() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_timestamp(1_622_678_400), "2021-06-03 00:00 UTC");
+    }
+
+    #[test]
+    fn test_history_lists_verdicts_per_run() {
+        let runs = vec![sample_run(0, false), sample_run(86400, true)];
+        assert_eq!(
+            get_test_history(&runs, "tests::a"),
+            "History of tests::a...\n\
+             #1 1970-01-01 00:00 UTC  nightly_1 agree, nightly_2 agree, nightly_3 agree\n\
+             #2 1970-01-02 00:00 UTC  nightly_1 agree, nightly_2 agree, nightly_3 dissent\n"
+        );
+    }
+}
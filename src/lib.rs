@@ -1,12 +1,32 @@
 use fasthash::sea;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::env;
 use std::fmt::Debug;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::str;
 use string_builder::Builder;
 
+pub mod artifact;
+pub mod baseline;
+pub mod bench;
+pub mod bisect;
+pub mod build;
+pub mod diagnostics;
+pub mod diff;
+pub mod history;
+pub mod measure;
+pub mod report;
+pub mod reproduce;
+pub mod runner;
+pub mod toolchain;
+pub mod triage;
+pub mod voter;
+
 #[derive(thiserror::Error, Debug)]
 pub enum ReferendumError {
     #[error("Test run failed to execute")]
@@ -15,36 +35,193 @@ pub enum ReferendumError {
     TestResultExtractionFailure(),
     #[error("Tests not found failure")]
     TestNotFound(),
+    #[error("Toolkit {0} is not installed")]
+    ToolchainNotFound(String),
+    #[error("Failed to access run history: {0}")]
+    HistoryFailure(String),
+    #[error("Failed to access baseline: {0}")]
+    BaselineFailure(String),
+    #[error("Bisection failed: {0}")]
+    BisectFailure(String),
+    #[error("Build failed: {0}")]
+    BuildFailure(String),
+    #[error("Artifact comparison failed: {0}")]
+    ArtifactFailure(String),
+    #[error("Reproducibility check failed: {0}")]
+    ReproducibilityFailure(String),
+    #[error("Benchmark comparison failed: {0}")]
+    BenchFailure(String),
+    #[error("Voter {0} is not available: {1}")]
+    VoterUnavailable(String, String),
+    #[error("Runner failed: {0}")]
+    RunnerFailure(String),
+    #[error("Toolkit {0} is invalid: {1}")]
+    InvalidToolkit(String, String),
 }
 
 pub type Result<T> = std::result::Result<T, ReferendumError>;
 
-fn run_tests(toolkit: &str) -> Result<String> {
-    let output = Command::new("rustup")
-        .arg("run")
-        .arg(toolkit)
-        .arg("cargo")
-        .arg("test")
-        .arg("--")
-        .arg("--test-threads=1")
-        .arg("--show-output")
-        .output()
-        .expect("Error running command");
-
-    match &output.status.success() {
-        false => {
-            //want to add information about the error here too
-            return Err(ReferendumError::TestRunFailure());
+//everything referendum writes lives under the cargo target directory
+pub fn referendum_dir() -> PathBuf {
+    let target = env::var_os("CARGO_TARGET_DIR").unwrap_or_else(|| "target".into());
+    PathBuf::from(target).join("referendum")
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub filter: Option<String>,
+    pub dir: Option<PathBuf>,
+    pub env: Vec<(String, String)>,
+    pub runner: runner::RunnerKind,
+}
+
+//a toolkit is a rustup toolchain, a sysroot such as build/host/stage1, or `rustc=<path>,cargo=<path>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Toolkit {
+    Rustup(String),
+    Paths {
+        rustc: PathBuf,
+        //cargo from PATH drives the rustc when the toolkit has none
+        cargo: Option<PathBuf>,
+    },
+}
+
+fn absolute(path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    match path.is_absolute() {
+        true => path,
+        false => env::current_dir().unwrap_or_default().join(path),
+    }
+}
+
+fn is_path_spec(toolkit: &str) -> bool {
+    toolkit
+        .split(',')
+        .any(|part| part.starts_with("rustc=") || part.starts_with("cargo="))
+}
+
+//`rustc=<path>,cargo=<path>` in either order, cargo may be left out
+fn parse_paths(toolkit: &str) -> Result<Toolkit> {
+    let invalid = |reason: String| ReferendumError::InvalidToolkit(toolkit.to_string(), reason);
+    let mut rustc = None;
+    let mut cargo = None;
+    for part in toolkit.split(',') {
+        match part.split_once('=') {
+            Some((key, "")) if key == "rustc" || key == "cargo" => {
+                return Err(invalid(format!("{}= needs a path", key)))
+            }
+            Some(("rustc", path)) => rustc = Some(absolute(path)),
+            Some(("cargo", path)) => cargo = Some(absolute(path)),
+            _ => {
+                return Err(invalid(format!(
+                    "`{}` is not rustc=<path> or cargo=<path>",
+                    part
+                )))
+            }
         }
-        true => (),
-    };
+    }
+    match rustc {
+        Some(rustc) => Ok(Toolkit::Paths { rustc, cargo }),
+        None => Err(invalid("rustc=<path> is missing".to_string())),
+    }
+}
 
-    match str::from_utf8(&output.stdout) {
-        Ok(v) => Ok(v.to_string()),
-        Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
+//parse_toolkit cannot fail, so a malformed toolkit is reported here before anything runs
+pub fn check_toolkit(toolkit: &str) -> Result<()> {
+    match is_path_spec(toolkit) {
+        true => parse_paths(toolkit).map(|_| ()),
+        false => Ok(()),
     }
 }
 
+pub fn parse_toolkit(toolkit: &str) -> Toolkit {
+    if is_path_spec(toolkit) {
+        if let Ok(paths) = parse_paths(toolkit) {
+            return paths;
+        }
+    }
+    if toolkit.starts_with('.') || toolkit.chars().any(std::path::is_separator) {
+        let bin = absolute(toolkit).join("bin");
+        let cargo = bin.join("cargo");
+        return Toolkit::Paths {
+            rustc: bin.join("rustc"),
+            cargo: match cargo.is_file() {
+                true => Some(cargo),
+                false => None,
+            },
+        };
+    }
+    Toolkit::Rustup(toolkit.to_string())
+}
+
+impl Toolkit {
+    //runs a program from the toolkit, e.g. cargo or rustc
+    pub fn command(&self, program: &str) -> Command {
+        let (rustc, cargo) = match self {
+            Toolkit::Rustup(name) => {
+                let mut command = Command::new("rustup");
+                command.arg("run").arg(name).arg(program);
+                return command;
+            }
+            Toolkit::Paths { rustc, cargo } => (rustc, cargo),
+        };
+        match program {
+            "cargo" => {
+                let mut command = Command::new(cargo.as_deref().unwrap_or(Path::new("cargo")));
+                //doctests need the matching rustdoc, which sits next to a built rustc
+                let rustdoc = rustc.with_file_name("rustdoc");
+                if rustdoc.is_file() {
+                    command.env("RUSTDOC", rustdoc);
+                }
+                command.env("RUSTC", rustc);
+                command
+            }
+            "rustc" => Command::new(rustc),
+            _ => Command::new(rustc.with_file_name(program)),
+        }
+    }
+}
+
+pub(crate) fn toolkit_command(toolkit: &str, program: &str) -> Command {
+    parse_toolkit(toolkit).command(program)
+}
+
+//a command as it would be typed into a shell, for reproducing what referendum ran
+pub(crate) fn command_line(command: &Command) -> String {
+    let mut parts: Vec<String> = command
+        .get_envs()
+        .filter_map(|(key, value)| {
+            Some(format!(
+                "{}={}",
+                key.to_string_lossy(),
+                value?.to_string_lossy()
+            ))
+        })
+        .collect();
+    parts.push(command.get_program().to_string_lossy().to_string());
+    parts.extend(
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string()),
+    );
+    parts.join(" ")
+}
+
+//a directory name for per toolkit build output, toolkits may be paths
+pub(crate) fn toolkit_dir(toolkit: &str) -> String {
+    toolkit
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
+                true => c,
+                false => '-',
+            },
+        )
+        .collect::<String>()
+        .trim_matches(|c| c == '-' || c == '.')
+        .to_string()
+}
+
 fn parse_test_output(output: &str) -> Vec<String> {
     let lines: Vec<String> = output.split('\n').map(|x| x.to_string()).collect();
 
@@ -113,24 +290,33 @@ fn generate_output_map(lines: &str) -> HashMap<String, String> {
 }
 
 fn get_consensus_hash(tests: &[Test]) -> Option<u64> {
-    let mut map: HashMap<u64, u8> = HashMap::new();
+    //counts are kept in toolkit order so ties resolve to the earliest toolkit
+    let mut counts: Vec<(u64, u8)> = Vec::new();
     for test in tests {
-        let count = map.entry(test.hash).or_insert(0);
-        *count += 1;
+        match counts.iter_mut().find(|(hash, _)| *hash == test.hash) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((test.hash, 1)),
+        }
     }
 
-    let max_hash = map.iter().max_by(|a, b| a.1.cmp(b.1)).unwrap();
+    let max_hash = counts
+        .iter()
+        .fold(None, |max: Option<&(u64, u8)>, entry| match max {
+            Some(max) if max.1 >= entry.1 => Some(max),
+            _ => Some(entry),
+        })
+        .unwrap();
 
     match max_hash.1 {
         1 => None,
-        _ => Some(*max_hash.0),
+        _ => Some(max_hash.0),
     }
 }
 
 pub fn vote(tests: Vec<Test>) -> Result<VoteResult> {
-    let mut test_map: HashMap<String, Vec<Test>> = HashMap::new();
+    let mut test_map: BTreeMap<String, Vec<Test>> = BTreeMap::new();
     for test in tests {
-        let entry = test_map.entry(test.name.clone()).or_insert_with(Vec::new);
+        let entry = test_map.entry(test.name.clone()).or_default();
         entry.push(test);
     }
 
@@ -168,29 +354,43 @@ pub fn vote(tests: Vec<Test>) -> Result<VoteResult> {
     })
 }
 
+pub fn hash_outcome(result: bool, output: &str) -> u64 {
+    sea::hash64((result.to_string() + output).as_bytes())
+}
+
 pub fn get_tests(toolkits: Vec<&str>) -> Result<Vec<Test>> {
+    get_tests_with(toolkits, &RunOptions::default())
+}
+
+//votes from libtest's human output, run with --show-output so passing tests keep their output
+pub fn parse_tests(toolkit: &str, run: &str) -> Result<Vec<Test>> {
+    let mut tests: Vec<Test> = Vec::new();
+    let output_map = generate_output_map(run);
+    let lines = parse_test_output(run);
+    let unique_test_names = get_test_names(&lines);
+    for test in unique_test_names.iter() {
+        let test_output = match output_map.get(test) {
+            Some(v) => v.to_string(),
+            None => "".to_string(),
+        };
+        let test_result = get_test_result(test, &lines)?;
+        let output_obj = Test {
+            name: test.clone(),
+            toolkit: toolkit.to_string(),
+            result: test_result,
+            output: test_output.clone(),
+            hash: hash_outcome(test_result, &test_output),
+            ..Default::default()
+        };
+        tests.push(output_obj);
+    }
+    Ok(tests)
+}
+
+pub fn get_tests_with(toolkits: Vec<&str>, options: &RunOptions) -> Result<Vec<Test>> {
     let mut tests: Vec<Test> = Vec::new();
-    //should I add something here to check that if a toolkit is installed in rustup
     for kit in toolkits {
-        let run = &run_tests(kit)?;
-        let output_map = generate_output_map(run);
-        let lines = parse_test_output(run);
-        let unique_test_names = get_test_names(&lines);
-        for test in unique_test_names.iter() {
-            let test_output = match output_map.get(test) {
-                Some(v) => v.to_string(),
-                None => "".to_string(),
-            };
-            let test_result = get_test_result(test, &lines)?;
-            let output_obj = Test {
-                name: test.clone(),
-                toolkit: kit.to_string(),
-                result: test_result,
-                output: test_output.clone(),
-                hash: sea::hash64((test_result.to_string() + &test_output).as_bytes()),
-            };
-            tests.push(output_obj);
-        }
+        tests.extend(voter::get_voter_tests(&voter::parse_voter(kit), options)?);
     }
     Ok(tests)
 }
@@ -215,6 +415,13 @@ fn generate_test_result_output(name: &str, result: bool, toolkit: Option<&str>)
     builder.string().unwrap()
 }
 
+fn duration_suffix(test: &Test) -> String {
+    match test.duration {
+        Some(seconds) => format!(" ({:.3}s)", seconds),
+        None => String::new(),
+    }
+}
+
 fn generate_test_output_output(name: &str, output: &str, toolkit: Option<&str>) -> String {
     let mut builder = Builder::default();
     builder.append("\n\t---- test ");
@@ -232,8 +439,8 @@ fn generate_test_output_output(name: &str, output: &str, toolkit: Option<&str>)
     builder.string().unwrap()
 }
 
-pub fn generate_consensus_map(consensus_votes: &[Test]) -> HashMap<String, Consensus> {
-    let mut consensus_map: HashMap<String, Consensus> = HashMap::new();
+pub fn generate_consensus_map(consensus_votes: &[Test]) -> BTreeMap<String, Consensus> {
+    let mut consensus_map: BTreeMap<String, Consensus> = BTreeMap::new();
     for matched_vote in consensus_votes.iter() {
         if !consensus_map.contains_key(&matched_vote.name.to_string()) {
             let consensus = Consensus {
@@ -247,7 +454,7 @@ pub fn generate_consensus_map(consensus_votes: &[Test]) -> HashMap<String, Conse
     consensus_map
 }
 
-pub fn get_consensus_results(consensus_map: &HashMap<String, Consensus>) -> String {
+pub fn get_consensus_results(consensus_map: &BTreeMap<String, Consensus>) -> String {
     let mut builder = Builder::default();
     builder.append("Consensus Test Results...\n");
     for (name, vote) in consensus_map.iter() {
@@ -271,7 +478,7 @@ pub fn get_consensus_results(consensus_map: &HashMap<String, Consensus>) -> Stri
 
 pub fn get_dissenting_results(
     dissenting_votes: Vec<Test>,
-    consensus_map: &HashMap<String, Consensus>,
+    consensus_map: &BTreeMap<String, Consensus>,
 ) -> String {
     let mut builder = Builder::default();
     builder.append("Dissenting Test Results...\n");
@@ -292,6 +499,7 @@ pub fn get_dissenting_results(
             dissenting_vote.result,
             Some(&dissenting_vote.toolkit),
         ));
+        builder.append(duration_suffix(dissenting_vote));
 
         builder.append(generate_test_output_output(
             &consensus.name,
@@ -318,6 +526,7 @@ pub fn get_no_consensus_results(no_consensus_votes: Vec<Test>) -> String {
             vote.result,
             Some(&vote.toolkit),
         ));
+        builder.append(duration_suffix(vote));
         builder.append(generate_test_output_output(
             &vote.name,
             &vote.output,
@@ -328,23 +537,48 @@ pub fn get_no_consensus_results(no_consensus_votes: Vec<Test>) -> String {
     builder.string().unwrap()
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Test {
     pub name: String,
     pub toolkit: String,
     pub result: bool,
     pub output: String,
     pub hash: u64,
+    //seconds the test took, when the runner reports it
+    #[serde(default)]
+    pub duration: Option<f64>,
+    //problems a checking voter reported for this test, e.g. undefined behavior under miri
+    #[serde(default)]
+    pub findings: Vec<Finding>,
+}
+
+#[cfg(test)]
+pub(crate) fn sample_test(name: &str, toolkit: &str, result: bool, output: &str) -> Test {
+    Test {
+        name: name.to_string(),
+        toolkit: toolkit.to_string(),
+        result,
+        output: output.to_string(),
+        hash: hash_outcome(result, output),
+        ..Default::default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    pub tool: String,
+    pub kind: String,
+    pub message: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Consensus {
     pub name: String,
     pub result: bool,
     pub output: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct VoteResult {
     pub matches: Vec<Test>,
     pub non_matches: Vec<Test>,
@@ -355,6 +589,71 @@ pub struct VoteResult {
 mod tests {
     use super::*;
 
+    #[test]
+    fn toolkits_may_be_paths() {
+        assert_eq!(
+            parse_toolkit("nightly-2021-06-02"),
+            Toolkit::Rustup("nightly-2021-06-02".to_string())
+        );
+        assert_eq!(
+            parse_toolkit("/opt/rust/build/host/stage1"),
+            Toolkit::Paths {
+                rustc: PathBuf::from("/opt/rust/build/host/stage1/bin/rustc"),
+                cargo: None,
+            }
+        );
+        assert_eq!(
+            parse_toolkit("rustc=/usr/bin/rustc,cargo=/usr/bin/cargo"),
+            Toolkit::Paths {
+                rustc: PathBuf::from("/usr/bin/rustc"),
+                cargo: Some(PathBuf::from("/usr/bin/cargo")),
+            }
+        );
+        assert_eq!(
+            parse_toolkit("cargo=/usr/bin/cargo,rustc=/usr/bin/rustc"),
+            parse_toolkit("rustc=/usr/bin/rustc,cargo=/usr/bin/cargo")
+        );
+    }
+
+    #[test]
+    fn malformed_path_toolkits_are_reported() {
+        let reason = |toolkit: &str| match check_toolkit(toolkit) {
+            Err(ReferendumError::InvalidToolkit(_, reason)) => reason,
+            other => panic!("unexpected check result {:?}", other),
+        };
+        assert_eq!(reason("cargo=/usr/bin/cargo"), "rustc=<path> is missing");
+        assert_eq!(reason("rustc="), "rustc= needs a path");
+        assert_eq!(
+            reason("rustc=/usr/bin/rustc,rustdoc=/usr/bin/rustdoc"),
+            "`rustdoc=/usr/bin/rustdoc` is not rustc=<path> or cargo=<path>"
+        );
+        assert!(check_toolkit("rustc=/usr/bin/rustc").is_ok());
+        assert!(check_toolkit("nightly").is_ok());
+    }
+
+    #[test]
+    fn command_lines_show_the_toolkit() {
+        let mut command = parse_toolkit("rustc=/opt/rust/rustc").command("cargo");
+        command.arg("rustc").arg("--profile").arg("test");
+        assert_eq!(
+            command_line(&command),
+            "RUSTC=/opt/rust/rustc cargo rustc --profile test"
+        );
+        let mut command = parse_toolkit("nightly").command("cargo");
+        command.arg("rustc");
+        assert_eq!(command_line(&command), "rustup run nightly cargo rustc");
+    }
+
+    #[test]
+    fn toolkit_dirs_stay_inside_the_target() {
+        assert_eq!(toolkit_dir("nightly-2021-06-02"), "nightly-2021-06-02");
+        assert_eq!(
+            toolkit_dir("/opt/rust/build/host/stage1"),
+            "opt-rust-build-host-stage1"
+        );
+        assert_eq!(toolkit_dir("rustc=/usr/bin/rustc"), "rustc--usr-bin-rustc");
+    }
+
     #[test]
     fn parse_single_test() {
         let input = "test tests::test_1 ... ok";
@@ -385,6 +684,7 @@ mod tests {
             result: true,
             output: "test output".to_string(),
             hash: 42,
+            ..Default::default()
         };
 
         let test_2 = Test {
@@ -393,6 +693,7 @@ mod tests {
             result: false,
             output: "test output".to_string(),
             hash: 42,
+            ..Default::default()
         };
 
         let test_3 = Test {
@@ -401,6 +702,7 @@ mod tests {
             result: false,
             output: "test output".to_string(),
             hash: 12,
+            ..Default::default()
         };
 
         let tests: Vec<Test> = vec![test_1, test_2, test_3];
@@ -413,7 +715,7 @@ mod tests {
         let test_name = "testing::test_name";
         let lines = ["test testing::test_name ... ok".to_string()];
 
-        assert!(get_test_result(&test_name, &lines).unwrap());
+        assert!(get_test_result(test_name, &lines).unwrap());
     }
 
     #[test]
@@ -421,7 +723,7 @@ mod tests {
         let test_name = "testing::test_name";
         let lines = ["test testing::test_name - should panic ... ok".to_string()];
 
-        assert!(get_test_result(&test_name, &lines).unwrap());
+        assert!(get_test_result(test_name, &lines).unwrap());
     }
 
     #[test]
@@ -432,6 +734,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 42,
+            ..Default::default()
         };
         let test_2 = Test {
             name: "test_name".to_string(),
@@ -439,6 +742,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 42,
+            ..Default::default()
         };
         let test_3 = Test {
             name: "test_name".to_string(),
@@ -446,6 +750,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 42,
+            ..Default::default()
         };
         let tests = vec![test_1, test_2, test_3];
         let votes = vote(tests).unwrap();
@@ -462,6 +767,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 42,
+            ..Default::default()
         };
         let test_2 = Test {
             name: "test_name".to_string(),
@@ -469,6 +775,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 42,
+            ..Default::default()
         };
         let test_3 = Test {
             name: "test_name".to_string(),
@@ -476,6 +783,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 12,
+            ..Default::default()
         };
         let tests = vec![test_1, test_2, test_3];
         let votes = vote(tests).unwrap();
@@ -484,6 +792,50 @@ mod tests {
         assert_eq!(votes.no_consensus.len(), 0);
     }
 
+    #[test]
+    fn vote_orders_by_test_then_toolkit() {
+        let tests = vec![
+            sample_test("tests::b", "nightly_2", true, "same"),
+            sample_test("tests::c", "nightly_2", true, "same"),
+            sample_test("tests::a", "nightly_2", true, "same"),
+            sample_test("tests::b", "nightly_1", true, "same"),
+            sample_test("tests::c", "nightly_1", true, "changed"),
+            sample_test("tests::a", "nightly_1", true, "same"),
+            sample_test("tests::c", "nightly_3", true, "same"),
+        ];
+        let votes = vote(tests).unwrap();
+        let order: Vec<(&str, &str)> = votes
+            .matches
+            .iter()
+            .map(|test| (test.name.as_str(), test.toolkit.as_str()))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("tests::a", "nightly_2"),
+                ("tests::a", "nightly_1"),
+                ("tests::b", "nightly_2"),
+                ("tests::b", "nightly_1"),
+                ("tests::c", "nightly_2"),
+                ("tests::c", "nightly_3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn consensus_tie_goes_to_first_toolkit() {
+        let tests = vec![
+            sample_test("test_name", "nightly_1", true, "first"),
+            sample_test("test_name", "nightly_2", true, "second"),
+            sample_test("test_name", "nightly_3", true, "second"),
+            sample_test("test_name", "nightly_4", true, "first"),
+        ];
+        assert_eq!(
+            get_consensus_hash(&tests),
+            Some(hash_outcome(true, "first"))
+        );
+    }
+
     #[test]
     fn vote_no_consensus() {
         let test_1 = Test {
@@ -492,6 +844,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 42,
+            ..Default::default()
         };
         let test_2 = Test {
             name: "test_name".to_string(),
@@ -499,6 +852,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 44,
+            ..Default::default()
         };
         let test_3 = Test {
             name: "test_name".to_string(),
@@ -506,6 +860,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 12,
+            ..Default::default()
         };
         let tests = vec![test_1, test_2, test_3];
         let votes = vote(tests).unwrap();
@@ -516,29 +871,36 @@ mod tests {
 
     #[test]
     fn test_output_generation() {
-        let output =
-            generate_test_output_output(&"test_name", &"this is the output", Some("tester"));
+        let output = generate_test_output_output("test_name", "this is the output", Some("tester"));
         let expected = "\n\t---- test test_name @ tester stdout ----\n\tthis is the output\n";
         assert_eq!(output, expected);
     }
 
     #[test]
     fn test_output_generation_no_output() {
-        let output = generate_test_output_output(&"test_name", &"", Some("tester"));
+        let output = generate_test_output_output("test_name", "", Some("tester"));
         let expected = "\n\t---- test test_name @ tester stdout ----\n\t\n";
         assert_eq!(output, expected);
     }
 
     #[test]
     fn test_pass_result_generation() {
-        let output = generate_test_result_output(&"test_name", true, Some("tester"));
+        let output = generate_test_result_output("test_name", true, Some("tester"));
         let expected = "test test_name @ tester ... ok";
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn durations_are_shown_when_known() {
+        let mut test = sample_test("tests::a", "nightly", true, "");
+        assert_eq!(duration_suffix(&test), "");
+        test.duration = Some(0.0125);
+        assert_eq!(duration_suffix(&test), " (0.013s)");
+    }
+
     #[test]
     fn test_failure_result_generation() {
-        let output = generate_test_result_output(&"test_name", false, Some("tester"));
+        let output = generate_test_result_output("test_name", false, Some("tester"));
         let expected = "test test_name @ tester ... FAILED";
         assert_eq!(output, expected);
     }
@@ -551,6 +913,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 42,
+            ..Default::default()
         };
         let test_2 = Test {
             name: "test_name".to_string(),
@@ -558,6 +921,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 44,
+            ..Default::default()
         };
         let test_3 = Test {
             name: "test_name".to_string(),
@@ -565,6 +929,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 42,
+            ..Default::default()
         };
         let tests = vec![test_1, test_2, test_3];
 
@@ -580,6 +945,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 42,
+            ..Default::default()
         };
         let test_2 = Test {
             name: "test_name".to_string(),
@@ -587,6 +953,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 44,
+            ..Default::default()
         };
         let test_3 = Test {
             name: "test_name".to_string(),
@@ -594,6 +961,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 42,
+            ..Default::default()
         };
         let tests = vec![test_1, test_2, test_3];
         let map = generate_consensus_map(&tests);
@@ -609,6 +977,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 42,
+            ..Default::default()
         };
         let test_2 = Test {
             name: "test_name".to_string(),
@@ -616,6 +985,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 44,
+            ..Default::default()
         };
         let test_3 = Test {
             name: "test_name".to_string(),
@@ -623,6 +993,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 42,
+            ..Default::default()
         };
         let test_4 = test_2.clone();
         let tests = vec![test_1, test_2, test_3];
@@ -639,6 +1010,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 12,
+            ..Default::default()
         };
         let test_2 = Test {
             name: "test_name".to_string(),
@@ -646,6 +1018,7 @@ mod tests {
             result: true,
             output: "this is the output".to_string(),
             hash: 44,
+            ..Default::default()
         };
         let tests = vec![test_1, test_2];
         assert_eq!(get_no_consensus_results(tests),
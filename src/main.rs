@@ -1,5 +1,32 @@
-use clap::{crate_version, App, Arg, SubCommand};
+use cargo_referendum::artifact::{diff_artifacts, get_artifact_differential_results};
+use cargo_referendum::baseline::{
+    check_baseline, get_baseline_check_results, load_baseline, save_baseline, Baseline,
+    DEFAULT_BASELINE,
+};
+use cargo_referendum::bench::{bench_toolkits, get_bench_results, MIN_BENCH_RUNS};
+use cargo_referendum::bisect::git::{bisect_revisions, get_revision_bisection_results};
+use cargo_referendum::bisect::mir::{bisect_mir_passes, get_mir_bisection_results};
+use cargo_referendum::bisect::opt::{bisect_opt_passes, get_opt_bisection_results};
+use cargo_referendum::bisect::{bisect_toolchains, get_toolchain_bisection_results};
+use cargo_referendum::diagnostics::{get_diagnostic_results, get_diagnostics, vote_diagnostics};
+use cargo_referendum::history::{
+    get_history_listing, get_test_history, history_path, load_runs, record_run, Run,
+};
+use cargo_referendum::measure::{get_measurement_results, get_outliers, measure_toolkits};
+use cargo_referendum::report::html::{get_html_diagnostics, get_html_report, get_html_triage};
+use cargo_referendum::report::markdown::{
+    get_markdown_diagnostics, get_markdown_report, get_markdown_triage, COMMENT_LIMIT,
+};
+use cargo_referendum::report::{colour_enabled, get_summary_report};
+use cargo_referendum::reproduce::{check_reproducibility, get_reproducibility_results};
+use cargo_referendum::runner::RunnerKind;
+use cargo_referendum::toolchain::{get_toolchains_with, Toolchain};
+use cargo_referendum::triage::{get_triage_results, triage_dissents};
+use cargo_referendum::voter::plain_toolkit;
 use cargo_referendum::*;
+use clap::{crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::fs;
+use std::path::Path;
 use std::process::exit;
 
 fn main() {
@@ -12,56 +39,681 @@ fn main() {
             SubCommand::with_name("referendum")
                 .about("Differential testing tool for unit tests")
                 .version(concat!("version: ", crate_version!()))
-                .arg(Arg::with_name("toolkits").required(true).min_values(1)),
+                .setting(AppSettings::SubcommandsNegateReqs)
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["human", "html", "markdown"])
+                        .default_value("human")
+                        .help("Report format"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .help("Write the report to a file instead of stdout"),
+                )
+                .arg(
+                    Arg::with_name("diagnostics")
+                        .long("diagnostics")
+                        .help("Also vote on compiler warnings and errors"),
+                )
+                .arg(
+                    Arg::with_name("runner")
+                        .long("runner")
+                        .takes_value(true)
+                        .possible_values(&["cargo", "nextest"])
+                        .default_value("cargo")
+                        .help("How plain toolkits build and run the tests, subcommands always use cargo"),
+                )
+                .arg(
+                    Arg::with_name("triage")
+                        .long("triage")
+                        .takes_value(true)
+                        .value_name("nightly toolkit")
+                        .help("Rerun dissenting tests under miri and AddressSanitizer with this toolkit"),
+                )
+                .arg(
+                    Arg::with_name("no-history")
+                        .long("no-history")
+                        .help("Do not record this run in the history"),
+                )
+                .arg(Arg::with_name("toolkits").required(true).min_values(1))
+                .subcommand(
+                    SubCommand::with_name("history")
+                        .about("Lists past runs, or one test's verdicts over time")
+                        .arg(
+                            Arg::with_name("test")
+                                .long("test")
+                                .takes_value(true)
+                                .help("Show the verdicts of a single test across runs"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("baseline")
+                        .about("Manages recorded consensus baselines")
+                        .setting(AppSettings::SubcommandRequiredElseHelp)
+                        .subcommand(
+                            SubCommand::with_name("save")
+                                .about("Runs a referendum and saves its consensus as a baseline")
+                                .arg(
+                                    Arg::with_name("output")
+                                        .long("output")
+                                        .short("o")
+                                        .takes_value(true)
+                                        .default_value(DEFAULT_BASELINE)
+                                        .help("Baseline file to write"),
+                                )
+                                .arg(Arg::with_name("toolkits").required(true).min_values(1)),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("check")
+                        .about("Compares a single toolkit against a saved baseline")
+                        .arg(
+                            Arg::with_name("baseline")
+                                .long("baseline")
+                                .takes_value(true)
+                                .default_value(DEFAULT_BASELINE)
+                                .help("Baseline file to compare against"),
+                        )
+                        .arg(Arg::with_name("toolkit").required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("bisect")
+                        .about("Finds the first toolkit, by commit date, where tests change")
+                        .arg(
+                            Arg::with_name("test")
+                                .long("test")
+                                .takes_value(true)
+                                .required(true)
+                                .help("Filter selecting the tests to bisect on"),
+                        )
+                        .arg(Arg::with_name("toolkits").required(true).min_values(2)),
+                )
+                .subcommand(
+                    SubCommand::with_name("bisect-revisions")
+                        .about("Finds the first commit of this repository where a test stops being unanimous")
+                        .arg(
+                            Arg::with_name("test")
+                                .long("test")
+                                .takes_value(true)
+                                .required(true)
+                                .help("Test to hold the referendum on"),
+                        )
+                        .arg(
+                            Arg::with_name("good")
+                                .long("good")
+                                .takes_value(true)
+                                .required(true)
+                                .help("Revision where the referendum is unanimous"),
+                        )
+                        .arg(
+                            Arg::with_name("bad")
+                                .long("bad")
+                                .takes_value(true)
+                                .default_value("HEAD")
+                                .help("Revision where the referendum is not unanimous"),
+                        )
+                        .arg(Arg::with_name("toolkits").required(true).min_values(2)),
+                )
+                .subcommand(
+                    SubCommand::with_name("opt-bisect")
+                        .about("Finds the LLVM pass at which a test's output diverges from opt-level 0")
+                        .arg(
+                            Arg::with_name("test")
+                                .long("test")
+                                .takes_value(true)
+                                .required(true)
+                                .help("Exact name of the dissenting test"),
+                        )
+                        .arg(
+                            Arg::with_name("bin")
+                                .long("bin")
+                                .takes_value(true)
+                                .help("Binary target containing the test, instead of the library"),
+                        )
+                        .arg(Arg::with_name("toolkit").required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("mir-bisect")
+                        .about("Finds the MIR passes whose disabling restores consensus for a test")
+                        .arg(
+                            Arg::with_name("test")
+                                .long("test")
+                                .takes_value(true)
+                                .required(true)
+                                .help("Exact name of the dissenting test"),
+                        )
+                        .arg(
+                            Arg::with_name("reference")
+                                .long("reference")
+                                .takes_value(true)
+                                .required(true)
+                                .help("Toolkit whose output is the consensus"),
+                        )
+                        .arg(
+                            Arg::with_name("passes")
+                                .long("passes")
+                                .takes_value(true)
+                                .use_delimiter(true)
+                                .help("Candidate MIR passes, instead of every pass the toolkit runs"),
+                        )
+                        .arg(
+                            Arg::with_name("bin")
+                                .long("bin")
+                                .takes_value(true)
+                                .help("Binary target containing the test, instead of the library"),
+                        )
+                        .arg(Arg::with_name("toolkit").required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("artifact-diff")
+                        .about("Diffs the assembly and LLVM IR behind a dissenting test")
+                        .arg(
+                            Arg::with_name("test")
+                                .long("test")
+                                .takes_value(true)
+                                .required(true)
+                                .help("Exact name of the dissenting test"),
+                        )
+                        .arg(
+                            Arg::with_name("bin")
+                                .long("bin")
+                                .takes_value(true)
+                                .help("Binary target containing the test, instead of the library"),
+                        )
+                        .arg(
+                            Arg::with_name("limit")
+                                .long("limit")
+                                .takes_value(true)
+                                .default_value("10")
+                                .help("Number of changed functions to show per artifact"),
+                        )
+                        .arg(Arg::with_name("toolkits").min_values(2).required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("reproduce")
+                        .about("Builds the tests repeatedly with one toolkit and compares the artifacts")
+                        .arg(
+                            Arg::with_name("builds")
+                                .long("builds")
+                                .takes_value(true)
+                                .default_value("2")
+                                .help("Number of clean builds to compare"),
+                        )
+                        .arg(Arg::with_name("toolkit").required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("measure")
+                        .about("Compares compile time and test binary sizes across toolkits")
+                        .arg(
+                            Arg::with_name("runs")
+                                .long("runs")
+                                .takes_value(true)
+                                .default_value("3")
+                                .help("Timed rebuilds per toolkit, the median is reported"),
+                        )
+                        .arg(
+                            Arg::with_name("threshold")
+                                .long("threshold")
+                                .takes_value(true)
+                                .default_value("25")
+                                .help("Percent deviation from the median that is flagged"),
+                        )
+                        .arg(Arg::with_name("toolkits").min_values(2).required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("bench")
+                        .about("Compares benchmark timings of each toolkit against the first")
+                        .arg(
+                            Arg::with_name("runs")
+                                .long("runs")
+                                .takes_value(true)
+                                .default_value("5")
+                                .help("Times cargo bench is run per toolkit"),
+                        )
+                        .arg(
+                            Arg::with_name("alpha")
+                                .long("alpha")
+                                .takes_value(true)
+                                .default_value("0.05")
+                                .help("Significance level of the t-test"),
+                        )
+                        .arg(
+                            Arg::with_name("threshold")
+                                .long("threshold")
+                                .takes_value(true)
+                                .default_value("5")
+                                .help("Smallest percent change reported as a regression"),
+                        )
+                        .arg(Arg::with_name("toolkits").min_values(2).required(true)),
+                ),
         )
         .get_matches();
 
-    let mut toolkits: Vec<_> = Vec::new();
-    if let Some(args) = args.subcommand_matches("referendum") {
-        toolkits = args.values_of("toolkits").unwrap().collect();
-    } else {
-        exit(1);
+    let args = match args.subcommand_matches("referendum") {
+        Some(args) => args,
+        None => exit(1),
+    };
+
+    match args.subcommand() {
+        ("history", Some(history_args)) => run_history(history_args),
+        ("baseline", Some(baseline_args)) => match baseline_args.subcommand() {
+            ("save", Some(save_args)) => run_baseline_save(save_args),
+            _ => exit(1),
+        },
+        ("check", Some(check_args)) => run_check(check_args),
+        ("bisect", Some(bisect_args)) => run_bisect(bisect_args),
+        ("bisect-revisions", Some(bisect_args)) => run_bisect_revisions(bisect_args),
+        ("opt-bisect", Some(bisect_args)) => run_opt_bisect(bisect_args),
+        ("mir-bisect", Some(bisect_args)) => run_mir_bisect(bisect_args),
+        ("artifact-diff", Some(diff_args)) => run_artifact_diff(diff_args),
+        ("reproduce", Some(reproduce_args)) => run_reproduce(reproduce_args),
+        ("measure", Some(measure_args)) => run_measure(measure_args),
+        ("bench", Some(bench_args)) => run_bench(bench_args),
+        _ => run_referendum(args),
     }
+}
 
-    let old_toolkits = vec![
-        "nightly-2021-06-03-x86_64-apple-darwin",
-        "nightly-x86_64-apple-darwin",
-    ];
+fn get_toolchains_or_exit(toolkits: &[&str]) -> Vec<Toolchain> {
+    get_toolchains_with_or_exit(toolkits, &RunOptions::default())
+}
 
-    //check that all toolkits are installed before running this
-    let tests = match get_tests(toolkits) {
+fn get_toolchains_with_or_exit(toolkits: &[&str], options: &RunOptions) -> Vec<Toolchain> {
+    //capturing toolchain identities also checks that every toolkit is installed
+    match get_toolchains_with(toolkits, options) {
         Ok(v) => v,
         Err(e) => {
             println!("{}", e);
             exit(1);
         }
-    };
-    let votes = match vote(tests) {
+    }
+}
+
+fn get_tests_or_exit(toolkits: &[&str]) -> Vec<Test> {
+    get_tests_with_or_exit(toolkits, &RunOptions::default())
+}
+
+fn get_tests_with_or_exit(toolkits: &[&str], options: &RunOptions) -> Vec<Test> {
+    match get_tests_with(toolkits.to_vec(), options) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn vote_or_exit(tests: Vec<Test>) -> VoteResult {
+    match vote(tests) {
         Ok(v) => v,
         Err(_e) => {
             println!("No tests found");
-            std::process::exit(1);
+            exit(1);
         }
+    }
+}
+
+fn run_referendum(args: &ArgMatches) {
+    let toolkits: Vec<_> = args.values_of("toolkits").unwrap().collect();
+    let options = RunOptions {
+        runner: match args.value_of("runner") {
+            Some("nextest") => RunnerKind::Nextest,
+            _ => RunnerKind::Cargo,
+        },
+        ..Default::default()
     };
+    //checked before any voter runs, a referendum is too expensive to throw away
+    //diagnostics rebuild the crate themselves, so only plain voters can take part
+    let diagnostic_toolkits = match args.is_present("diagnostics") {
+        true => match toolkits
+            .iter()
+            .map(|spec| plain_toolkit(spec, "--diagnostics"))
+            .collect::<Result<Vec<String>>>()
+        {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("{}", e);
+                exit(1);
+            }
+        },
+        false => None,
+    };
+    let toolchains = get_toolchains_with_or_exit(&toolkits, &options);
+    let votes = vote_or_exit(get_tests_with_or_exit(&toolkits, &options));
 
     let consensus_map = generate_consensus_map(&votes.matches);
 
-    if !votes.matches.is_empty() {
-        println!("{}", get_consensus_results(&consensus_map));
-    } else {
-        println!("No consensus determined among test ouputs ...\n");
+    let diagnostics = match &diagnostic_toolkits {
+        Some(kits) => {
+            match get_diagnostics(&kits.iter().map(|kit| kit.as_str()).collect::<Vec<&str>>()) {
+                Ok(v) => Some(vote_diagnostics(v)),
+                Err(e) => {
+                    println!("{}", e);
+                    exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let triages = match args.value_of("triage") {
+        Some(toolkit) if !votes.non_matches.is_empty() => match triage_dissents(&votes, toolkit) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("{}", e);
+                exit(1);
+            }
+        },
+        _ => None,
+    };
+
+    let mut report = match args.value_of("format") {
+        Some("html") => {
+            let sections: Vec<String> = diagnostics
+                .iter()
+                .map(get_html_diagnostics)
+                .chain(triages.iter().map(|triages| get_html_triage(triages)))
+                .collect();
+            get_html_report(&votes, &consensus_map, &toolchains, &sections)
+        }
+        Some("markdown") => get_markdown_report(&votes, &consensus_map, &toolchains, COMMENT_LIMIT),
+        _ => get_summary_report(
+            &votes,
+            &consensus_map,
+            &toolchains,
+            args.value_of("output").is_none() && colour_enabled(),
+        ),
+    };
+    if let Some(diagnostics) = &diagnostics {
+        match args.value_of("format") {
+            Some("html") => (),
+            Some("markdown") => report.push_str(&get_markdown_diagnostics(diagnostics)),
+            _ => {
+                report.push('\n');
+                report.push_str(&get_diagnostic_results(diagnostics));
+            }
+        }
+    }
+    if let Some(triages) = &triages {
+        match args.value_of("format") {
+            Some("html") => (),
+            Some("markdown") => report.push_str(&get_markdown_triage(triages)),
+            _ => {
+                report.push('\n');
+                report.push_str(&get_triage_results(triages));
+            }
+        }
     }
 
-    if !votes.non_matches.is_empty() {
-        println!(
+    match args.value_of("output") {
+        Some(path) => {
+            if let Err(e) = fs::write(path, report) {
+                println!("Failed to write report to {}: {}", path, e);
+                exit(1);
+            }
+        }
+        None => println!("{}", report),
+    }
+
+    if !args.is_present("no-history") {
+        if let Err(e) = record_run(&history_path(), &Run::new(toolchains, votes)) {
+            println!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn run_history(args: &ArgMatches) {
+    let runs = match load_runs(&history_path()) {
+        Ok((runs, unreadable)) => {
+            if !unreadable.is_empty() {
+                println!(
+                    "Skipped unreadable history lines: {}",
+                    unreadable
+                        .iter()
+                        .map(|line| line.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                );
+            }
+            runs
+        }
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    };
+
+    match args.value_of("test") {
+        Some(test) => println!("{}", get_test_history(&runs, test)),
+        None => println!("{}", get_history_listing(&runs)),
+    }
+}
+
+fn run_baseline_save(args: &ArgMatches) {
+    let toolkits: Vec<_> = args.values_of("toolkits").unwrap().collect();
+    let toolchains = get_toolchains_or_exit(&toolkits);
+    let votes = vote_or_exit(get_tests_or_exit(&toolkits));
+
+    let baseline = Baseline {
+        toolchains,
+        consensus: generate_consensus_map(&votes.matches),
+    };
+    let path = args.value_of("output").unwrap();
+    if let Err(e) = save_baseline(Path::new(path), &baseline) {
+        println!("{}", e);
+        exit(1);
+    }
+    println!(
+        "Saved consensus for {} tests to {}",
+        baseline.consensus.len(),
+        path
+    );
+}
+
+fn run_check(args: &ArgMatches) {
+    let baseline = match load_baseline(Path::new(args.value_of("baseline").unwrap())) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    };
+    let toolkit = args.value_of("toolkit").unwrap();
+    get_toolchains_or_exit(&[toolkit]);
+
+    let check = check_baseline(&baseline, get_tests_or_exit(&[toolkit]));
+    println!("{}", get_baseline_check_results(&check, &baseline));
+    if !check.is_clean() {
+        exit(1);
+    }
+}
+
+fn run_bisect(args: &ArgMatches) {
+    let toolkits: Vec<_> = args.values_of("toolkits").unwrap().collect();
+    match bisect_toolchains(&toolkits, args.value_of("test").unwrap()) {
+        Ok(bisection) => println!("{}", get_toolchain_bisection_results(&bisection)),
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn run_bisect_revisions(args: &ArgMatches) {
+    let toolkits: Vec<_> = args.values_of("toolkits").unwrap().collect();
+    get_toolchains_or_exit(&toolkits);
+    match bisect_revisions(
+        &toolkits,
+        args.value_of("test").unwrap(),
+        args.value_of("good").unwrap(),
+        args.value_of("bad").unwrap(),
+    ) {
+        Ok(bisection) => println!("{}", get_revision_bisection_results(&bisection)),
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn get_target_selection(args: &ArgMatches) -> Vec<String> {
+    match args.value_of("bin") {
+        Some(bin) => vec!["--bin".to_string(), bin.to_string()],
+        None => vec!["--lib".to_string()],
+    }
+}
+
+fn run_opt_bisect(args: &ArgMatches) {
+    let toolkit = args.value_of("toolkit").unwrap();
+    get_toolchains_or_exit(&[toolkit]);
+    match bisect_opt_passes(
+        toolkit,
+        args.value_of("test").unwrap(),
+        &get_target_selection(args),
+    ) {
+        Ok(bisection) => println!("{}", get_opt_bisection_results(&bisection)),
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn run_mir_bisect(args: &ArgMatches) {
+    let toolkit = args.value_of("toolkit").unwrap();
+    let reference = args.value_of("reference").unwrap();
+    get_toolchains_or_exit(&[toolkit, reference]);
+    let candidates: Option<Vec<String>> = args
+        .values_of("passes")
+        .map(|passes| passes.map(|pass| pass.to_string()).collect());
+    match bisect_mir_passes(
+        toolkit,
+        reference,
+        args.value_of("test").unwrap(),
+        &get_target_selection(args),
+        candidates.as_deref(),
+    ) {
+        Ok(bisection) => println!("{}", get_mir_bisection_results(&bisection)),
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn run_artifact_diff(args: &ArgMatches) {
+    let toolkits: Vec<_> = args.values_of("toolkits").unwrap().collect();
+    get_toolchains_or_exit(&toolkits);
+    let limit = match args.value_of("limit").unwrap().parse::<usize>() {
+        Ok(limit) => limit,
+        Err(_) => {
+            println!("--limit must be a number");
+            exit(1);
+        }
+    };
+    match diff_artifacts(
+        &toolkits,
+        args.value_of("test").unwrap(),
+        &get_target_selection(args),
+    ) {
+        Ok(differential) => println!(
             "{}",
-            get_dissenting_results(votes.non_matches, &consensus_map)
-        );
-    } else {
-        println!("No dissenting test outputs found ...\n");
+            get_artifact_differential_results(&differential, limit)
+        ),
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn run_reproduce(args: &ArgMatches) {
+    let toolkit = args.value_of("toolkit").unwrap();
+    get_toolchains_or_exit(&[toolkit]);
+    let builds = match args.value_of("builds").unwrap().parse::<usize>() {
+        Ok(builds) => builds,
+        Err(_) => {
+            println!("--builds must be a number");
+            exit(1);
+        }
+    };
+    match check_reproducibility(toolkit, builds) {
+        Ok(reproducibility) => {
+            println!("{}", get_reproducibility_results(&reproducibility));
+            if !reproducibility.is_reproducible() {
+                exit(1);
+            }
+        }
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
     }
+}
 
-    if !votes.no_consensus.is_empty() {
-        println!("{}", get_no_consensus_results(votes.no_consensus));
+fn run_measure(args: &ArgMatches) {
+    let toolkits: Vec<_> = args.values_of("toolkits").unwrap().collect();
+    get_toolchains_or_exit(&toolkits);
+    let (runs, threshold) = match (
+        args.value_of("runs").unwrap().parse::<usize>(),
+        args.value_of("threshold").unwrap().parse::<f64>(),
+    ) {
+        (Ok(runs), Ok(threshold)) if runs > 0 => (runs, threshold),
+        _ => {
+            println!("--runs must be a positive number and --threshold a number");
+            exit(1);
+        }
+    };
+    match measure_toolkits(&toolkits, runs) {
+        Ok(measurements) => {
+            println!("{}", get_measurement_results(&measurements, threshold));
+            if !get_outliers(&measurements, threshold).is_empty() {
+                exit(1);
+            }
+        }
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn run_bench(args: &ArgMatches) {
+    let toolkits: Vec<_> = args.values_of("toolkits").unwrap().collect();
+    let (runs, alpha, threshold) = match (
+        args.value_of("runs").unwrap().parse::<usize>(),
+        args.value_of("alpha").unwrap().parse::<f64>(),
+        args.value_of("threshold").unwrap().parse::<f64>(),
+    ) {
+        (Ok(runs), Ok(alpha), Ok(threshold)) if runs >= MIN_BENCH_RUNS => (runs, alpha, threshold),
+        (Ok(_), Ok(_), Ok(_)) => {
+            println!(
+                "--runs must be at least {}, a t-test needs more than one sample per toolkit",
+                MIN_BENCH_RUNS
+            );
+            exit(1);
+        }
+        _ => {
+            println!("--runs must be a number, --alpha and --threshold numbers");
+            exit(1);
+        }
+    };
+    get_toolchains_or_exit(&toolkits);
+    match bench_toolkits(&toolkits, runs, alpha, threshold) {
+        Ok(differential) => {
+            println!("{}", get_bench_results(&differential));
+            if differential.regressions() > 0 {
+                exit(1);
+            }
+        }
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
     }
 }
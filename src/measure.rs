@@ -0,0 +1,265 @@
+use crate::build::{clean_package, configure};
+use crate::{referendum_dir, toolkit_command, toolkit_dir, ReferendumError, Result, RunOptions};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::Instant;
+use string_builder::Builder;
+
+pub const COMPILE_TIME: &str = "compile time";
+
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub toolkit: String,
+    //metric to value, seconds for the compile time and bytes for test binaries
+    pub metrics: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deviation {
+    pub metric: String,
+    pub toolkit: String,
+    pub value: f64,
+    pub median: f64,
+}
+
+impl Deviation {
+    pub fn percent(&self) -> f64 {
+        match self.median == 0.0 {
+            true => 0.0,
+            false => (self.value - self.median) / self.median * 100.0,
+        }
+    }
+}
+
+fn measure_error(e: impl std::fmt::Display) -> ReferendumError {
+    ReferendumError::BuildFailure(e.to_string())
+}
+
+//test binaries keyed by target kind and name, the file names carry a per toolkit hash
+pub fn parse_test_binaries(messages: &str) -> Vec<(String, String)> {
+    messages
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|message| message["reason"] == "compiler-artifact")
+        .filter(|message| message["profile"]["test"] == true)
+        .filter_map(|message| {
+            let kind = message["target"]["kind"][0].as_str().unwrap_or("test");
+            let name = message["target"]["name"].as_str()?;
+            let executable = message["executable"].as_str()?;
+            Some((format!("{} {}", kind, name), executable.to_string()))
+        })
+        .collect()
+}
+
+fn build_tests(toolkit: &str, options: &RunOptions) -> Result<String> {
+    let mut command = toolkit_command(toolkit, "cargo");
+    command
+        .arg("test")
+        .arg("--no-run")
+        .arg("--message-format=json");
+    configure(&mut command, options);
+    let output = command.output().map_err(measure_error)?;
+    if !output.status.success() {
+        return Err(measure_error(format!(
+            "cargo test --no-run failed with {}",
+            toolkit
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+pub fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mid = sorted.len() / 2;
+    match sorted.len() {
+        0 => 0.0,
+        n if n % 2 == 0 => (sorted[mid - 1] + sorted[mid]) / 2.0,
+        _ => sorted[mid],
+    }
+}
+
+//dependencies are built once untimed, then the package itself is rebuilt `runs` times
+pub fn measure_toolkit(toolkit: &str, runs: usize) -> Result<Measurement> {
+    let options = RunOptions {
+        env: vec![(
+            "CARGO_TARGET_DIR".to_string(),
+            referendum_dir()
+                .join("measure")
+                .join(toolkit_dir(toolkit))
+                .to_string_lossy()
+                .to_string(),
+        )],
+        ..Default::default()
+    };
+    let mut messages = build_tests(toolkit, &options)?;
+    let mut times = Vec::new();
+    for _ in 0..runs {
+        clean_package(toolkit, &options)?;
+        let start = Instant::now();
+        messages = build_tests(toolkit, &options)?;
+        times.push(start.elapsed().as_secs_f64());
+    }
+
+    let mut metrics = BTreeMap::new();
+    metrics.insert(COMPILE_TIME.to_string(), median(&times));
+    for (name, executable) in parse_test_binaries(&messages) {
+        let size = fs::metadata(&executable).map_err(measure_error)?.len();
+        metrics.insert(format!("size {}", name), size as f64);
+    }
+    Ok(Measurement {
+        toolkit: toolkit.to_string(),
+        metrics,
+    })
+}
+
+pub fn measure_toolkits(toolkits: &[&str], runs: usize) -> Result<Vec<Measurement>> {
+    toolkits
+        .iter()
+        .map(|kit| measure_toolkit(kit, runs))
+        .collect()
+}
+
+fn metric_names(measurements: &[Measurement]) -> Vec<String> {
+    let mut names: Vec<String> = vec![COMPILE_TIME.to_string()];
+    for measurement in measurements.iter() {
+        for name in measurement.metrics.keys() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    names
+}
+
+//every toolkit's deviation from the median of each metric
+pub fn get_deviations(measurements: &[Measurement]) -> Vec<Deviation> {
+    let mut deviations = Vec::new();
+    for metric in metric_names(measurements) {
+        let values: Vec<(&str, f64)> = measurements
+            .iter()
+            .filter_map(|m| Some((m.toolkit.as_str(), *m.metrics.get(&metric)?)))
+            .collect();
+        let middle = median(&values.iter().map(|(_, value)| *value).collect::<Vec<_>>());
+        for (toolkit, value) in values {
+            deviations.push(Deviation {
+                metric: metric.clone(),
+                toolkit: toolkit.to_string(),
+                value,
+                median: middle,
+            });
+        }
+    }
+    deviations
+}
+
+pub fn get_outliers(measurements: &[Measurement], threshold: f64) -> Vec<Deviation> {
+    get_deviations(measurements)
+        .into_iter()
+        .filter(|deviation| deviation.percent().abs() > threshold)
+        .collect()
+}
+
+fn format_value(metric: &str, value: f64) -> String {
+    match metric == COMPILE_TIME {
+        true => format!("{:.2}s", value),
+        false => format!("{:.1}KiB", value / 1024.0),
+    }
+}
+
+pub fn get_measurement_results(measurements: &[Measurement], threshold: f64) -> String {
+    let deviations = get_deviations(measurements);
+    let outliers = get_outliers(measurements, threshold);
+    let mut builder = Builder::default();
+    builder.append("Compile Time and Size Results...\n");
+    for metric in metric_names(measurements) {
+        let rows: Vec<&Deviation> = deviations
+            .iter()
+            .filter(|deviation| deviation.metric == metric)
+            .collect();
+        let median = match rows.first() {
+            Some(row) => row.median,
+            None => continue,
+        };
+        builder.append(format!(
+            "{} (median {})\n",
+            metric,
+            format_value(&metric, median)
+        ));
+        for row in rows {
+            builder.append(format!(
+                "\t{} {} ... {:+.1}%{}\n",
+                row.toolkit,
+                format_value(&metric, row.value),
+                row.percent(),
+                if outliers.contains(row) {
+                    " DEVIATES"
+                } else {
+                    ""
+                }
+            ));
+        }
+    }
+    builder.append(format!(
+        "{} measurements deviate from the median by more than {}%\n",
+        outliers.len(),
+        threshold
+    ));
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measurement(toolkit: &str, time: f64, size: f64) -> Measurement {
+        let mut metrics = BTreeMap::new();
+        metrics.insert(COMPILE_TIME.to_string(), time);
+        metrics.insert("size lib sample".to_string(), size);
+        Measurement {
+            toolkit: toolkit.to_string(),
+            metrics,
+        }
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(&[3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&[4.0, 1.0, 2.0, 3.0]), 2.5);
+    }
+
+    #[test]
+    fn test_binaries_are_named_by_target() {
+        let messages = r#"{"reason":"compiler-artifact","target":{"kind":["lib"],"name":"sample"},"profile":{"test":false},"executable":null}
+{"reason":"compiler-artifact","target":{"kind":["lib"],"name":"sample"},"profile":{"test":true},"executable":"/work/target/debug/deps/sample-0123"}
+{"reason":"compiler-artifact","target":{"kind":["test"],"name":"integration"},"profile":{"test":true},"executable":"/work/target/debug/deps/integration-4567"}"#;
+        assert_eq!(
+            parse_test_binaries(messages),
+            vec![
+                (
+                    "lib sample".to_string(),
+                    "/work/target/debug/deps/sample-0123".to_string()
+                ),
+                (
+                    "test integration".to_string(),
+                    "/work/target/debug/deps/integration-4567".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn doubled_compile_time_is_an_outlier() {
+        let measurements = vec![
+            measurement("stable", 10.0, 1000.0),
+            measurement("beta", 11.0, 1010.0),
+            measurement("nightly", 22.0, 1000.0),
+        ];
+        let outliers = get_outliers(&measurements, 25.0);
+        assert_eq!(outliers.len(), 1);
+        assert_eq!(outliers[0].toolkit, "nightly");
+        assert_eq!(outliers[0].metric, COMPILE_TIME);
+        assert_eq!(outliers[0].percent(), 100.0);
+    }
+}
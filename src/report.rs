@@ -0,0 +1,399 @@
+use crate::toolchain::Toolchain;
+use crate::{
+    get_dissenting_results, get_no_consensus_results, Consensus, Finding, Test, VoteResult,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::IsTerminal;
+use string_builder::Builder;
+
+pub mod html;
+pub mod markdown;
+
+const GREEN: &str = "32";
+const RED: &str = "31";
+const YELLOW: &str = "33";
+const MAGENTA: &str = "35";
+const DIM: &str = "2";
+const BOLD: &str = "1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Agree,
+    Fail,
+    Dissent,
+    NoConsensus,
+    Missing,
+}
+
+impl Verdict {
+    fn marker(self) -> &'static str {
+        match self {
+            Verdict::Agree => ".",
+            Verdict::Fail => "F",
+            Verdict::Dissent => "D",
+            Verdict::NoConsensus => "?",
+            Verdict::Missing => "-",
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Verdict::Agree => "agree",
+            Verdict::Fail => "fail",
+            Verdict::Dissent => "dissent",
+            Verdict::NoConsensus => "no-consensus",
+            Verdict::Missing => "missing",
+        }
+    }
+
+    fn colour(self) -> &'static str {
+        match self {
+            Verdict::Agree => GREEN,
+            Verdict::Fail => YELLOW,
+            Verdict::Dissent => RED,
+            Verdict::NoConsensus => MAGENTA,
+            Verdict::Missing => DIM,
+        }
+    }
+}
+
+//colour is only used when stdout is a terminal and NO_COLOR is unset
+pub fn colour_enabled() -> bool {
+    std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+}
+
+struct Painter {
+    enabled: bool,
+}
+
+impl Painter {
+    fn paint(&self, text: &str, code: &str) -> String {
+        match self.enabled {
+            true => format!("\x1b[{}m{}\x1b[0m", code, text),
+            false => text.to_string(),
+        }
+    }
+}
+
+pub fn generate_verdict_matrix(
+    votes: &VoteResult,
+    toolkits: &[&str],
+) -> Vec<(String, Vec<Verdict>)> {
+    let mut cells: HashMap<(&str, &str), Verdict> = HashMap::new();
+    let mut names: BTreeSet<&str> = BTreeSet::new();
+
+    for test in votes.matches.iter() {
+        let verdict = match test.result {
+            true => Verdict::Agree,
+            false => Verdict::Fail,
+        };
+        cells.insert((&test.name, &test.toolkit), verdict);
+        names.insert(&test.name);
+    }
+    for test in votes.non_matches.iter() {
+        cells.insert((&test.name, &test.toolkit), Verdict::Dissent);
+        names.insert(&test.name);
+    }
+    for test in votes.no_consensus.iter() {
+        cells.insert((&test.name, &test.toolkit), Verdict::NoConsensus);
+        names.insert(&test.name);
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            let row = toolkits
+                .iter()
+                .map(|kit| *cells.get(&(*name, *kit)).unwrap_or(&Verdict::Missing))
+                .collect();
+            (name.to_string(), row)
+        })
+        .collect()
+}
+
+pub(crate) fn toolchain_names(toolchains: &[Toolchain]) -> Vec<&str> {
+    toolchains.iter().map(|kit| kit.name.as_str()).collect()
+}
+
+//a test some toolkits did not vote on, without a dissent or a lack of consensus among those that did
+pub(crate) fn is_incomplete(row: &[Verdict]) -> bool {
+    row.contains(&Verdict::Missing)
+        && !row.contains(&Verdict::Dissent)
+        && !row.contains(&Verdict::NoConsensus)
+}
+
+pub(crate) fn is_problem(row: &[Verdict]) -> bool {
+    row.iter()
+        .any(|v| *v != Verdict::Agree && *v != Verdict::Fail)
+}
+
+//each finding with whether every other voter passed the test, which predicts future dissent
+pub(crate) fn collect_findings(votes: &VoteResult) -> Vec<(&Test, &Finding, bool)> {
+    let all: Vec<&Test> = votes
+        .matches
+        .iter()
+        .chain(votes.non_matches.iter())
+        .chain(votes.no_consensus.iter())
+        .collect();
+    let mut findings: Vec<(&Test, &Finding, bool)> = Vec::new();
+    for test in all.iter() {
+        let others: Vec<&&Test> = all
+            .iter()
+            .filter(|other| other.name == test.name && other.toolkit != test.toolkit)
+            .collect();
+        let passes_elsewhere = !others.is_empty() && others.iter().all(|other| other.result);
+        for finding in test.findings.iter() {
+            findings.push((test, finding, passes_elsewhere));
+        }
+    }
+    findings.sort_by(|a, b| (&a.0.name, &a.0.toolkit).cmp(&(&b.0.name, &b.0.toolkit)));
+    findings
+}
+
+pub(crate) fn describe_finding(test: &Test, finding: &Finding, passes_elsewhere: bool) -> String {
+    let mut line = format!(
+        "{} @ {}: {}: {}",
+        test.name, test.toolkit, finding.kind, finding.message
+    );
+    if passes_elsewhere {
+        line.push_str(" (passes on every other toolkit)");
+    }
+    line
+}
+
+fn result_label(result: bool) -> &'static str {
+    match result {
+        true => "ok",
+        false => "FAILED",
+    }
+}
+
+pub fn get_summary_report(
+    votes: &VoteResult,
+    consensus_map: &BTreeMap<String, Consensus>,
+    toolchains: &[Toolchain],
+    colour: bool,
+) -> String {
+    let painter = Painter { enabled: colour };
+    let toolkits = toolchain_names(toolchains);
+    let matrix = generate_verdict_matrix(votes, &toolkits);
+    let mut builder = Builder::default();
+
+    let dissenting_tests = matrix
+        .iter()
+        .filter(|(_, row)| row.contains(&Verdict::Dissent))
+        .count();
+    let no_consensus_tests = matrix
+        .iter()
+        .filter(|(_, row)| row.contains(&Verdict::NoConsensus))
+        .count();
+    let unanimous_tests = matrix.iter().filter(|(_, row)| !is_problem(row)).count();
+    let incomplete_tests = matrix.iter().filter(|(_, row)| is_incomplete(row)).count();
+
+    builder.append(painter.paint("Referendum Summary...", BOLD));
+    builder.append("\n");
+    builder.append(format!(
+        "{} tests across {} toolkits: {} unanimous, {} with dissent, {} without consensus, {} with missing votes\n",
+        matrix.len(),
+        toolkits.len(),
+        painter.paint(&unanimous_tests.to_string(), GREEN),
+        painter.paint(&dissenting_tests.to_string(), RED),
+        painter.paint(&no_consensus_tests.to_string(), MAGENTA),
+        painter.paint(&incomplete_tests.to_string(), DIM),
+    ));
+    builder.append(format!(
+        "{} consensus votes, {} dissenting votes, {} votes without consensus\n\n",
+        votes.matches.len(),
+        votes.non_matches.len(),
+        votes.no_consensus.len(),
+    ));
+
+    let findings = collect_findings(votes);
+    if !findings.is_empty() {
+        builder.append(painter.paint(
+            &format!("{} findings from checking voters...", findings.len()),
+            RED,
+        ));
+        builder.append("\n");
+        for (test, finding, passes_elsewhere) in findings {
+            builder.append(format!(
+                "  {}\n",
+                painter.paint(&describe_finding(test, finding, passes_elsewhere), RED)
+            ));
+        }
+        builder.append("\n");
+    }
+
+    for (i, kit) in toolchains.iter().enumerate() {
+        builder.append(format!(
+            "  [{}] {} {}\n",
+            i + 1,
+            kit.name,
+            painter.paint(&format!("({})", kit.describe()), DIM)
+        ));
+    }
+    builder.append("\n");
+
+    let name_width = matrix
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        .max("dissents".len());
+
+    builder.append(format!("{:width$} ", "", width = name_width));
+    for i in 0..toolkits.len() {
+        builder.append(format!(" {:>3}", format!("[{}]", i + 1)));
+    }
+    builder.append("\n");
+
+    for (name, row) in matrix.iter() {
+        builder.append(format!("{:width$} ", name, width = name_width));
+        for verdict in row.iter() {
+            builder.append(format!(
+                " {}",
+                painter.paint(&format!("{:>3}", verdict.marker()), verdict.colour())
+            ));
+        }
+        builder.append("\n");
+    }
+
+    builder.append(format!("{:width$} ", "dissents", width = name_width));
+    for i in 0..toolkits.len() {
+        let count = matrix
+            .iter()
+            .filter(|(_, row)| row[i] == Verdict::Dissent)
+            .count();
+        let cell = format!("{:>3}", count);
+        match count {
+            0 => builder.append(format!(" {}", cell)),
+            _ => builder.append(format!(" {}", painter.paint(&cell, RED))),
+        }
+    }
+    builder.append("\n\n");
+
+    builder.append(painter.paint(
+        ". agree   F fail   D dissent   ? no consensus   - missing",
+        DIM,
+    ));
+    builder.append("\n");
+
+    if !votes.non_matches.is_empty() {
+        builder.append("\n");
+        builder.append(get_dissenting_results(
+            votes.non_matches.clone(),
+            consensus_map,
+        ));
+    }
+
+    if !votes.no_consensus.is_empty() {
+        builder.append("\n");
+        builder.append(get_no_consensus_results(votes.no_consensus.clone()));
+    }
+
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toolchain::sample_toolchain;
+    use crate::{generate_consensus_map, sample_test, vote};
+
+    #[test]
+    fn matrix_marks_every_verdict() {
+        let tests = vec![
+            sample_test("tests::a", "nightly_1", true, "same"),
+            sample_test("tests::a", "nightly_2", true, "same"),
+            sample_test("tests::a", "nightly_3", true, "changed"),
+            sample_test("tests::b", "nightly_1", false, "failed"),
+            sample_test("tests::b", "nightly_2", false, "failed"),
+        ];
+        let votes = vote(tests).unwrap();
+        let matrix = generate_verdict_matrix(&votes, &["nightly_1", "nightly_2", "nightly_3"]);
+
+        assert_eq!(
+            matrix,
+            vec![
+                (
+                    "tests::a".to_string(),
+                    vec![Verdict::Agree, Verdict::Agree, Verdict::Dissent]
+                ),
+                (
+                    "tests::b".to_string(),
+                    vec![Verdict::Fail, Verdict::Fail, Verdict::Missing]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn summary_totals_count_missing_votes() {
+        let tests = vec![
+            sample_test("tests::a", "nightly_1", true, "same"),
+            sample_test("tests::a", "nightly_2", true, "same"),
+            sample_test("tests::b", "nightly_1", true, "same"),
+            sample_test("tests::b", "nightly_2", true, "same"),
+            sample_test("tests::b", "nightly_3", true, "same"),
+        ];
+        let votes = vote(tests).unwrap();
+        let map = generate_consensus_map(&votes.matches);
+        let toolchains: Vec<Toolchain> = ["nightly_1", "nightly_2", "nightly_3"]
+            .iter()
+            .map(|kit| sample_toolchain(kit))
+            .collect();
+        let report = get_summary_report(&votes, &map, &toolchains, false);
+        assert!(report.contains("2 tests across 3 toolkits: 1 unanimous, 0 with dissent, 0 without consensus, 1 with missing votes\n"));
+    }
+
+    #[test]
+    fn summary_without_colour_has_no_escapes() {
+        let tests = vec![
+            sample_test("tests::a", "nightly_1", true, "same"),
+            sample_test("tests::a", "nightly_2", true, "same"),
+            sample_test("tests::a", "nightly_3", true, "changed"),
+        ];
+        let votes = vote(tests).unwrap();
+        let map = generate_consensus_map(&votes.matches);
+        let toolchains: Vec<Toolchain> = ["nightly_1", "nightly_2", "nightly_3"]
+            .iter()
+            .map(|kit| sample_toolchain(kit))
+            .collect();
+        let report = get_summary_report(&votes, &map, &toolchains, false);
+
+        assert!(!report.contains('\x1b'));
+        assert!(report.contains("1 tests across 3 toolkits: 0 unanimous, 1 with dissent, 0 without consensus, 0 with missing votes\n"));
+        assert!(report.contains("  [3] nightly_3 (rustc 1.55.0-nightly (b41936b92 2021-06-02), LLVM 12.0.1, x86_64-apple-darwin, cargo 1.54.0-nightly (0cecbd673 2021-06-01))\n"));
+        assert!(report.contains("tests::a    .   .   D\n"));
+        assert!(report.contains("dissents    0   0   1\n"));
+        assert!(report.contains("Dissenting Test Results..."));
+        assert!(!report.contains("Consensus Test Results..."));
+    }
+
+    #[test]
+    fn findings_note_when_every_other_toolkit_passes() {
+        let mut ub = sample_test("tests::a", "miri:nightly", false, "dangling");
+        ub.findings.push(Finding {
+            tool: "miri".to_string(),
+            kind: "Undefined Behavior".to_string(),
+            message: "pointer is dangling".to_string(),
+        });
+        let tests = vec![
+            sample_test("tests::a", "nightly_1", true, "same"),
+            sample_test("tests::a", "nightly_2", true, "same"),
+            ub,
+        ];
+        let votes = vote(tests).unwrap();
+        let findings = collect_findings(&votes);
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].2);
+
+        let map = generate_consensus_map(&votes.matches);
+        let toolchains: Vec<Toolchain> = ["nightly_1", "nightly_2", "miri:nightly"]
+            .iter()
+            .map(|kit| sample_toolchain(kit))
+            .collect();
+        let report = get_summary_report(&votes, &map, &toolchains, false);
+        assert!(report.contains("1 findings from checking voters...\n  tests::a @ miri:nightly: Undefined Behavior: pointer is dangling (passes on every other toolkit)\n"));
+    }
+}
@@ -0,0 +1,416 @@
+use super::{
+    collect_findings, describe_finding, generate_verdict_matrix, is_incomplete, is_problem,
+    result_label, toolchain_names, Verdict,
+};
+use crate::diagnostics::DiagnosticVote;
+use crate::diff::{diff_lines, side_by_side};
+use crate::toolchain::Toolchain;
+use crate::triage::{describe_triage, Triage, TriageVerdict};
+use crate::{Consensus, Test, VoteResult};
+use std::collections::BTreeMap;
+use string_builder::Builder;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+h1 { margin-bottom: 0.2em; }
+.cards { display: flex; gap: 1em; margin: 1em 0; }
+.card { border: 1px solid #ccc; border-radius: 6px; padding: 0.6em 1.2em; }
+.card b { display: block; font-size: 1.8em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ddd; padding: 0.2em 0.6em; text-align: center; }
+td.name { text-align: left; font-family: monospace; }
+.agree { background: #dff5df; }
+.fail { background: #fff3c4; }
+.dissent { background: #f8d0d0; }
+.no-consensus { background: #ead7f5; }
+.missing { background: #eee; color: #999; }
+.findings { border: 2px solid #c00; border-radius: 6px; padding: 0.2em 1em; background: #fdeaea; }
+.filters { margin: 1em 0; }
+details { margin: 0.4em 0; border: 1px solid #ddd; border-radius: 4px; padding: 0.3em 0.6em; }
+summary { cursor: pointer; font-family: monospace; }
+pre { background: #f6f6f6; padding: 0.5em; overflow-x: auto; }
+table.diff td { text-align: left; font-family: monospace; white-space: pre; vertical-align: top; }
+table.diff td.removed { background: #f8d0d0; }
+table.diff td.added { background: #dff5df; }
+";
+
+const SCRIPT: &str = "
+function applyFilters() {
+  var text = document.getElementById('filter-text').value.toLowerCase();
+  var status = document.getElementById('filter-status').value;
+  document.querySelectorAll('[data-test]').forEach(function (el) {
+    var nameMatch = el.getAttribute('data-test').toLowerCase().indexOf(text) !== -1;
+    var statusMatch = status === 'all' || el.getAttribute('data-status').split(' ').indexOf(status) !== -1;
+    el.style.display = nameMatch && statusMatch ? '' : 'none';
+  });
+}
+";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn row_status(row: &[Verdict]) -> String {
+    let mut labels: Vec<&str> = row.iter().map(|verdict| verdict.label()).collect();
+    labels.sort_unstable();
+    labels.dedup();
+    labels.join(" ")
+}
+
+fn generate_diff_table(consensus: &str, dissent: &str, toolkit: &str) -> String {
+    let mut builder = Builder::default();
+    builder.append(format!(
+        "<table class=\"diff\"><tr><th>consensus</th><th>{}</th></tr>\n",
+        escape(toolkit)
+    ));
+    for (left, right) in side_by_side(&diff_lines(consensus, dissent)) {
+        let changed = left != right;
+        let cell = |text: Option<&str>, class: &str| match (text, changed) {
+            (Some(text), true) => format!("<td class=\"{}\">{}</td>", class, escape(text)),
+            (Some(text), false) => format!("<td>{}</td>", escape(text)),
+            (None, _) => "<td></td>".to_string(),
+        };
+        builder.append(format!(
+            "<tr>{}{}</tr>\n",
+            cell(left, "removed"),
+            cell(right, "added")
+        ));
+    }
+    builder.append("</table>\n");
+    builder.string().unwrap()
+}
+
+//diagnostics as a list of what each dissenting toolkit added or lost
+pub fn get_html_diagnostics(vote: &DiagnosticVote) -> String {
+    let mut builder = Builder::default();
+    builder.append("<h2>Diagnostics</h2>\n");
+    let consensus = match &vote.consensus {
+        Some(consensus) => consensus,
+        None => {
+            builder.append("<p><b>No consensus</b> on diagnostics</p>\n");
+            for (toolkit, diagnostics) in vote.no_consensus.iter() {
+                builder.append(format!(
+                    "<details><summary>{}</summary>\n<pre>",
+                    escape(toolkit)
+                ));
+                for (diagnostic, text) in diagnostics.iter() {
+                    builder.append(format!(
+                        "{} {}\n",
+                        escape(&diagnostic.to_string()),
+                        escape(text)
+                    ));
+                }
+                builder.append("</pre>\n</details>\n");
+            }
+            return builder.string().unwrap();
+        }
+    };
+
+    builder.append(format!(
+        "<p><b>{}</b> consensus diagnostics, <b>{}</b> toolkits dissent</p>\n",
+        consensus.len(),
+        vote.dissents.len()
+    ));
+    for dissent in vote.dissents.iter() {
+        builder.append(format!(
+            "<details><summary>{}: {} new, {} vanished</summary>\n<table class=\"diff\">\n",
+            escape(&dissent.toolkit),
+            dissent.new.len(),
+            dissent.vanished.len()
+        ));
+        for (diagnostic, text) in dissent.new.iter() {
+            builder.append(format!(
+                "<tr><td class=\"added\">+{} {}</td></tr>\n",
+                escape(&diagnostic.to_string()),
+                escape(text)
+            ));
+        }
+        for (diagnostic, text) in dissent.vanished.iter() {
+            builder.append(format!(
+                "<tr><td class=\"removed\">-{} {}</td></tr>\n",
+                escape(&diagnostic.to_string()),
+                escape(text)
+            ));
+        }
+        builder.append("</table>\n</details>\n");
+    }
+    builder.string().unwrap()
+}
+
+pub fn get_html_triage(triages: &[Triage]) -> String {
+    let mut builder = Builder::default();
+    builder.append("<h2>Dissent Triage</h2>\n<ul>\n");
+    for triage in triages.iter() {
+        let class = match triage.verdict() {
+            TriageVerdict::LikelyUb => "dissent",
+            TriageVerdict::NoUbDetected => "no-consensus",
+            TriageVerdict::Unchecked => "missing",
+        };
+        builder.append(format!(
+            "<li class=\"{}\">{}</li>\n",
+            class,
+            escape(&describe_triage(triage))
+        ));
+    }
+    builder.append("</ul>\n");
+    builder.string().unwrap()
+}
+
+//sections such as diagnostics are rendered after the tests
+pub fn get_html_report(
+    votes: &VoteResult,
+    consensus_map: &BTreeMap<String, Consensus>,
+    toolchains: &[Toolchain],
+    sections: &[String],
+) -> String {
+    let toolkits = toolchain_names(toolchains);
+    let matrix = generate_verdict_matrix(votes, &toolkits);
+    let mut outputs: BTreeMap<(&str, &str), &Test> = BTreeMap::new();
+    for test in votes
+        .matches
+        .iter()
+        .chain(votes.non_matches.iter())
+        .chain(votes.no_consensus.iter())
+    {
+        outputs.insert((&test.name, &test.toolkit), test);
+    }
+
+    let count = |verdict: Verdict| {
+        matrix
+            .iter()
+            .filter(|(_, row)| row.contains(&verdict))
+            .count()
+    };
+    let unanimous = matrix.iter().filter(|(_, row)| !is_problem(row)).count();
+
+    let mut builder = Builder::default();
+    builder.append("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    builder.append("<title>Referendum Report</title>\n<style>");
+    builder.append(STYLE);
+    builder.append("</style>\n<script>");
+    builder.append(SCRIPT);
+    builder.append("</script>\n</head>\n<body>\n<h1>Referendum Report</h1>\n");
+
+    builder.append("<div class=\"cards\">\n");
+    for (label, value, class) in [
+        ("tests", matrix.len(), ""),
+        ("toolkits", toolkits.len(), ""),
+        ("unanimous", unanimous, "agree"),
+        ("with dissent", count(Verdict::Dissent), "dissent"),
+        (
+            "without consensus",
+            count(Verdict::NoConsensus),
+            "no-consensus",
+        ),
+        (
+            "with missing votes",
+            matrix.iter().filter(|(_, row)| is_incomplete(row)).count(),
+            "missing",
+        ),
+    ]
+    .iter()
+    {
+        builder.append(format!(
+            "<div class=\"card {}\"><b>{}</b>{}</div>\n",
+            class, value, label
+        ));
+    }
+    builder.append("</div>\n");
+
+    let findings = collect_findings(votes);
+    if !findings.is_empty() {
+        builder.append("<div class=\"findings\">\n<h2>Findings</h2>\n<ul>\n");
+        for (test, finding, passes_elsewhere) in findings {
+            builder.append(format!(
+                "<li>{}</li>\n",
+                escape(&describe_finding(test, finding, passes_elsewhere))
+            ));
+        }
+        builder.append("</ul>\n</div>\n");
+    }
+
+    builder.append("<h2>Toolkits</h2>\n<table>\n");
+    builder.append(
+        "<tr><th>toolkit</th><th>rustc</th><th>commit</th><th>commit date</th><th>LLVM</th><th>host</th><th>cargo</th></tr>\n",
+    );
+    for kit in toolchains.iter() {
+        builder.append(format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape(&kit.name),
+            escape(&kit.release),
+            escape(kit.commit_hash.as_deref().unwrap_or("unknown")),
+            escape(kit.commit_date.as_deref().unwrap_or("unknown")),
+            escape(kit.llvm_version.as_deref().unwrap_or("unknown")),
+            escape(&kit.host),
+            escape(&kit.cargo_version)
+        ));
+    }
+    builder.append("</table>\n");
+
+    builder.append("<div class=\"filters\">\n");
+    builder.append(
+        "<input id=\"filter-text\" placeholder=\"filter tests\" oninput=\"applyFilters()\">\n",
+    );
+    builder.append("<select id=\"filter-status\" onchange=\"applyFilters()\">\n");
+    for status in ["all", "agree", "fail", "dissent", "no-consensus", "missing"].iter() {
+        builder.append(format!("<option>{}</option>\n", status));
+    }
+    builder.append("</select>\n</div>\n");
+
+    builder.append("<h2>Votes</h2>\n<table class=\"grid\">\n<tr><th>test</th>");
+    for kit in toolkits.iter() {
+        builder.append(format!("<th>{}</th>", escape(kit)));
+    }
+    builder.append("</tr>\n");
+    for (name, row) in matrix.iter() {
+        builder.append(format!(
+            "<tr data-test=\"{}\" data-status=\"{}\"><td class=\"name\">{}</td>",
+            escape(name),
+            row_status(row),
+            escape(name)
+        ));
+        for verdict in row.iter() {
+            builder.append(format!(
+                "<td class=\"{}\">{}</td>",
+                verdict.label(),
+                verdict.label()
+            ));
+        }
+        builder.append("</tr>\n");
+    }
+    builder.append("</table>\n");
+
+    builder.append("<h2>Tests</h2>\n");
+    for (name, row) in matrix.iter() {
+        let status = row_status(row);
+        builder.append(format!(
+            "<details data-test=\"{}\" data-status=\"{}\"><summary>{} <small>{}</small></summary>\n",
+            escape(name),
+            status,
+            escape(name),
+            status
+        ));
+
+        let consensus = consensus_map.get(name);
+        if let Some(consensus) = consensus {
+            builder.append(format!(
+                "<h4>consensus ... {}</h4>\n<pre>{}</pre>\n",
+                result_label(consensus.result),
+                escape(&consensus.output)
+            ));
+        }
+
+        for (kit, verdict) in toolkits.iter().zip(row.iter()) {
+            let test = match outputs.get(&(name.as_str(), *kit)) {
+                Some(test) => test,
+                None => continue,
+            };
+            match (verdict, consensus) {
+                (Verdict::Dissent, Some(consensus)) => {
+                    builder.append(format!(
+                        "<h4>{} ... {} (dissent)</h4>\n",
+                        escape(kit),
+                        result_label(test.result)
+                    ));
+                    builder.append(generate_diff_table(&consensus.output, &test.output, kit));
+                }
+                (Verdict::NoConsensus, _) => {
+                    builder.append(format!(
+                        "<h4>{} ... {}</h4>\n<pre>{}</pre>\n",
+                        escape(kit),
+                        result_label(test.result),
+                        escape(&test.output)
+                    ));
+                }
+                _ => (),
+            }
+        }
+        builder.append("</details>\n");
+    }
+
+    for section in sections.iter() {
+        builder.append(section.as_str());
+    }
+    builder.append("</body>\n</html>\n");
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{vote_diagnostics, Diagnostic, Diagnostics};
+    use crate::toolchain::sample_toolchain;
+    use crate::{generate_consensus_map, sample_test, vote};
+
+    fn vote_result() -> VoteResult {
+        vote(vec![sample_test("tests::a", "stable", true, "same")]).unwrap()
+    }
+
+    #[test]
+    fn html_report_is_self_contained() {
+        let tests = vec![
+            sample_test("tests::a", "nightly_1", true, "same"),
+            sample_test("tests::a", "nightly_2", true, "same"),
+            sample_test("tests::a", "nightly_3", true, "<changed>"),
+        ];
+        let votes = vote(tests).unwrap();
+        let map = generate_consensus_map(&votes.matches);
+        let toolchains: Vec<Toolchain> = ["nightly_1", "nightly_2", "nightly_3"]
+            .iter()
+            .map(|kit| sample_toolchain(kit))
+            .collect();
+        let report = get_html_report(&votes, &map, &toolchains, &[]);
+
+        assert!(!report.contains("http://") && !report.contains("https://"));
+        assert!(report.contains("<td>b41936b92cd8463020207cb2f62a4247942ef2e4</td>"));
+        assert!(report.contains("<td class=\"dissent\">dissent</td>"));
+        assert!(report.contains("<td class=\"removed\">same</td>"));
+        assert!(report.contains("<td class=\"added\">&lt;changed&gt;</td>"));
+    }
+
+    #[test]
+    fn html_diagnostics_list_new_and_vanished() {
+        let warning = |line: u64| Diagnostic {
+            file: "src/lib.rs".to_string(),
+            line,
+            column: 9,
+            level: "warning".to_string(),
+            code: Some("unused_variables".to_string()),
+        };
+        let set = |lines: &[u64]| -> Diagnostics {
+            lines
+                .iter()
+                .map(|line| (warning(*line), "unused variable: `x`".to_string()))
+                .collect()
+        };
+        let vote = vote_diagnostics(vec![
+            ("stable".to_string(), set(&[3])),
+            ("beta".to_string(), set(&[3])),
+            ("nightly".to_string(), set(&[7])),
+        ]);
+        let sections = vec![get_html_diagnostics(&vote)];
+        let report = get_html_report(&vote_result(), &BTreeMap::new(), &[], &sections);
+        let section = &sections[0];
+
+        assert!(section.contains("nightly: 1 new, 1 vanished"));
+        assert!(section.contains(
+            "<td class=\"added\">+warning[unused_variables] src/lib.rs:7:9 unused variable: `x`</td>"
+        ));
+        assert!(report.ends_with(&format!("{}</body>\n</html>\n", section)));
+    }
+
+    #[test]
+    fn html_triage_marks_likely_ub() {
+        let triage = Triage {
+            test: "tests::a".to_string(),
+            checked: vec!["miri".to_string()],
+            skipped: vec![("asan".to_string(), "<not installed>".to_string())],
+            findings: Vec::new(),
+        };
+        let section = get_html_triage(&[triage]);
+        assert!(section.contains("<li class=\"no-consensus\">tests::a ... no UB detected, possible compiler bug (miri clean; asan skipped: &lt;not installed&gt;)</li>"));
+    }
+}
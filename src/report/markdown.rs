@@ -0,0 +1,296 @@
+use super::{
+    collect_findings, describe_finding, generate_verdict_matrix, is_problem, result_label,
+    toolchain_names, Verdict,
+};
+use crate::diagnostics::DiagnosticVote;
+use crate::diff::unified_diff;
+use crate::toolchain::Toolchain;
+use crate::triage::{describe_triage, Triage, TriageVerdict};
+use crate::{Consensus, Test, VoteResult};
+use std::collections::BTreeMap;
+use string_builder::Builder;
+
+//github rejects comments over 65536 characters, leave room for a note
+pub const COMMENT_LIMIT: usize = 60_000;
+
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn optional_cell(value: &Option<String>) -> String {
+    match value {
+        Some(value) => format!("`{}`", escape_cell(value)),
+        None => "unknown".to_string(),
+    }
+}
+
+fn fence(text: &str) -> &'static str {
+    match text.contains("```") {
+        true => "~~~~",
+        false => "```",
+    }
+}
+
+fn generate_details(summary: &str, body: &str, language: &str) -> String {
+    let fence = fence(body);
+    format!(
+        "<details><summary>{}</summary>\n\n{}{}\n{}\n{}\n\n</details>\n",
+        summary,
+        fence,
+        language,
+        body.trim_end_matches('\n'),
+        fence
+    )
+}
+
+pub fn get_markdown_report(
+    votes: &VoteResult,
+    consensus_map: &BTreeMap<String, Consensus>,
+    toolchains: &[Toolchain],
+    limit: usize,
+) -> String {
+    let toolkits = toolchain_names(toolchains);
+    let matrix = generate_verdict_matrix(votes, &toolkits);
+    let problems: Vec<&(String, Vec<Verdict>)> =
+        matrix.iter().filter(|(_, row)| is_problem(row)).collect();
+
+    let mut header = Builder::default();
+    header.append("## Referendum results\n\n");
+    header
+        .append("| toolkit | rustc | commit | LLVM | host | cargo |\n|---|---|---|---|---|---|\n");
+    for kit in toolchains.iter() {
+        header.append(format!(
+            "| `{}` | `{}` | {} | {} | {} | `{}` |\n",
+            escape_cell(&kit.name),
+            escape_cell(&kit.release),
+            optional_cell(&kit.commit_hash),
+            optional_cell(&kit.llvm_version),
+            escape_cell(&kit.host),
+            escape_cell(&kit.cargo_version)
+        ));
+    }
+    header.append(format!(
+        "\n**{}** tests, **{}** unanimous, **{}** divergent\n",
+        matrix.len(),
+        matrix.len() - problems.len(),
+        problems.len()
+    ));
+    //findings go in the header so truncation never hides them
+    for (test, finding, passes_elsewhere) in collect_findings(votes) {
+        header.append(format!(
+            "\n> :warning: **{}**\n",
+            describe_finding(test, finding, passes_elsewhere)
+        ));
+    }
+
+    let mut sections: Vec<String> = Vec::new();
+    if !problems.is_empty() {
+        let mut table = Builder::default();
+        table.append("\n### Divergent tests\n\n| test |");
+        for kit in toolkits.iter() {
+            table.append(format!(" `{}` |", escape_cell(kit)));
+        }
+        table.append("\n|---|");
+        for _ in toolkits.iter() {
+            table.append("---|");
+        }
+        table.append("\n");
+        sections.push(table.string().unwrap());
+
+        for (name, row) in problems.iter() {
+            let mut line = format!("| `{}` |", escape_cell(name));
+            for verdict in row.iter() {
+                match verdict {
+                    Verdict::Dissent | Verdict::NoConsensus => {
+                        line.push_str(&format!(" **{}** |", verdict.label()))
+                    }
+                    _ => line.push_str(&format!(" {} |", verdict.label())),
+                }
+            }
+            line.push('\n');
+            sections.push(line);
+        }
+        sections.push("\n".to_string());
+    }
+
+    for test in votes.non_matches.iter() {
+        let consensus = match consensus_map.get(&test.name) {
+            Some(consensus) => consensus,
+            None => continue,
+        };
+        let body = format!(
+            "--- consensus ... {}\n+++ {} ... {}\n{}",
+            result_label(consensus.result),
+            test.toolkit,
+            result_label(test.result),
+            unified_diff(&consensus.output, &test.output)
+        );
+        sections.push(generate_details(
+            &format!("<code>{}</code> @ <code>{}</code>", test.name, test.toolkit),
+            &body,
+            "diff",
+        ));
+    }
+
+    let mut no_consensus: BTreeMap<&str, Vec<&Test>> = BTreeMap::new();
+    for test in votes.no_consensus.iter() {
+        no_consensus.entry(&test.name).or_default().push(test);
+    }
+    for (name, tests) in no_consensus.iter() {
+        let mut body = String::new();
+        for test in tests.iter() {
+            body.push_str(&format!(
+                "---- {} ... {}\n{}\n",
+                test.toolkit,
+                result_label(test.result),
+                test.output
+            ));
+        }
+        sections.push(generate_details(
+            &format!("<code>{}</code> has no consensus", name),
+            &body,
+            "",
+        ));
+    }
+
+    let mut report = header.string().unwrap();
+    for (i, section) in sections.iter().enumerate() {
+        if report.len() + section.len() > limit {
+            report.push_str(&format!(
+                "\n_Report truncated: {} of {} sections omitted to fit the comment limit. Run referendum locally for the full report._\n",
+                sections.len() - i,
+                sections.len()
+            ));
+            break;
+        }
+        report.push_str(section);
+    }
+    report
+}
+
+pub fn get_markdown_diagnostics(vote: &DiagnosticVote) -> String {
+    let mut builder = Builder::default();
+    builder.append("\n### Diagnostics\n\n");
+    let consensus = match &vote.consensus {
+        Some(consensus) => consensus,
+        None => {
+            builder.append("**No consensus** on diagnostics\n\n");
+            for (toolkit, diagnostics) in vote.no_consensus.iter() {
+                let body: Vec<String> = diagnostics
+                    .iter()
+                    .map(|(diagnostic, text)| format!("{} {}", diagnostic, text))
+                    .collect();
+                builder.append(generate_details(
+                    &format!("<code>{}</code>", toolkit),
+                    &body.join("\n"),
+                    "",
+                ));
+            }
+            return builder.string().unwrap();
+        }
+    };
+
+    builder.append(format!(
+        "**{}** consensus diagnostics, **{}** toolkits dissent\n\n",
+        consensus.len(),
+        vote.dissents.len()
+    ));
+    for dissent in vote.dissents.iter() {
+        let mut body: Vec<String> = Vec::new();
+        for (diagnostic, text) in dissent.new.iter() {
+            body.push(format!("+{} {}", diagnostic, text));
+        }
+        for (diagnostic, text) in dissent.vanished.iter() {
+            body.push(format!("-{} {}", diagnostic, text));
+        }
+        builder.append(generate_details(
+            &format!(
+                "<code>{}</code>: {} new, {} vanished",
+                dissent.toolkit,
+                dissent.new.len(),
+                dissent.vanished.len()
+            ),
+            &body.join("\n"),
+            "diff",
+        ));
+    }
+    builder.string().unwrap()
+}
+
+pub fn get_markdown_triage(triages: &[Triage]) -> String {
+    let mut builder = Builder::default();
+    builder.append("\n### Dissent Triage\n\n");
+    for triage in triages.iter() {
+        let marker = match triage.verdict() {
+            TriageVerdict::LikelyUb => ":warning:",
+            TriageVerdict::NoUbDetected => ":mag:",
+            TriageVerdict::Unchecked => ":grey_question:",
+        };
+        builder.append(format!("- {} {}\n", marker, describe_triage(triage)));
+    }
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toolchain::sample_toolchain;
+    use crate::{generate_consensus_map, sample_test, vote};
+
+    fn votes() -> VoteResult {
+        vote(vec![
+            sample_test("tests::a", "nightly_1", true, "same"),
+            sample_test("tests::a", "nightly_2", true, "same"),
+            sample_test("tests::a", "nightly_3", true, "changed"),
+        ])
+        .unwrap()
+    }
+
+    fn toolchains() -> Vec<Toolchain> {
+        ["nightly_1", "nightly_2", "nightly_3"]
+            .iter()
+            .map(|kit| sample_toolchain(kit))
+            .collect()
+    }
+
+    #[test]
+    fn markdown_report_lists_divergence() {
+        let votes = votes();
+        let map = generate_consensus_map(&votes.matches);
+        let report = get_markdown_report(&votes, &map, &toolchains(), COMMENT_LIMIT);
+
+        assert!(report.contains("| `nightly_1` | `rustc 1.55.0-nightly (b41936b92 2021-06-02)` | `b41936b92cd8463020207cb2f62a4247942ef2e4` | `12.0.1` | x86_64-apple-darwin | `cargo 1.54.0-nightly (0cecbd673 2021-06-01)` |"));
+        assert!(report.contains("| `tests::a` | agree | agree | **dissent** |"));
+        assert!(report
+            .contains("```diff\n--- consensus ... ok\n+++ nightly_3 ... ok\n-same\n+changed\n```"));
+        assert!(!report.contains("truncated"));
+    }
+
+    #[test]
+    fn markdown_report_truncates() {
+        let votes = votes();
+        let map = generate_consensus_map(&votes.matches);
+        let report = get_markdown_report(&votes, &map, &toolchains(), 800);
+
+        assert!(report.len() < 1100);
+        assert!(report.contains("Report truncated"));
+    }
+
+    #[test]
+    fn markdown_triage_marks_likely_ub() {
+        let triage = Triage {
+            test: "tests::a".to_string(),
+            checked: vec!["asan".to_string()],
+            skipped: Vec::new(),
+            findings: vec![crate::Finding {
+                tool: "asan".to_string(),
+                kind: "heap-use-after-free".to_string(),
+                message: "on address 0x6020".to_string(),
+            }],
+        };
+        assert_eq!(
+            get_markdown_triage(&[triage]),
+            "\n### Dissent Triage\n\n- :warning: tests::a ... likely UB in user code (asan: heap-use-after-free: on address 0x6020)\n"
+        );
+    }
+}
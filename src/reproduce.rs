@@ -0,0 +1,473 @@
+use crate::build::configure;
+use crate::{referendum_dir, toolkit_command, toolkit_dir, ReferendumError, Result, RunOptions};
+use fasthash::sea;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryInto;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use string_builder::Builder;
+
+//artifact path relative to the target directory to its content hash
+pub type BuildHashes = BTreeMap<String, u64>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NonReproducible {
+    pub path: String,
+    pub hashes: Vec<Option<u64>>,
+    pub sections: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Reproducibility {
+    pub toolkit: String,
+    pub builds: usize,
+    pub artifacts: usize,
+    pub non_reproducible: Vec<NonReproducible>,
+}
+
+impl Reproducibility {
+    pub fn is_reproducible(&self) -> bool {
+        self.non_reproducible.is_empty()
+    }
+}
+
+fn reproduce_error(e: impl std::fmt::Display) -> ReferendumError {
+    ReferendumError::ReproducibilityFailure(e.to_string())
+}
+
+//every file cargo reports for a compiler artifact, dependencies and build scripts included
+pub fn parse_artifact_files(messages: &str) -> Vec<PathBuf> {
+    let mut files: BTreeSet<PathBuf> = BTreeSet::new();
+    for message in messages
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|message| message["reason"] == "compiler-artifact")
+    {
+        if let Some(filenames) = message["filenames"].as_array() {
+            files.extend(
+                filenames
+                    .iter()
+                    .filter_map(|file| file.as_str())
+                    .map(PathBuf::from),
+            );
+        }
+        if let Some(executable) = message["executable"].as_str() {
+            files.insert(PathBuf::from(executable));
+        }
+    }
+    files.into_iter().collect()
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn unique(sections: &mut Vec<(String, u64)>, name: String, hash: u64) {
+    let mut key = name.clone();
+    let mut n = 1;
+    while sections.iter().any(|(existing, _)| *existing == key) {
+        n += 1;
+        key = format!("{} ({})", name, n);
+    }
+    sections.push((key, hash));
+}
+
+//section name to content hash for 64-bit little endian ELF files
+fn elf_sections(bytes: &[u8]) -> Option<Vec<(String, u64)>> {
+    if !bytes.starts_with(b"\x7fELF") || bytes.get(4) != Some(&2) || bytes.get(5) != Some(&1) {
+        return None;
+    }
+    let shoff = read_u64(bytes, 0x28)? as usize;
+    let shentsize = read_u16(bytes, 0x3a)? as usize;
+    let shnum = read_u16(bytes, 0x3c)? as usize;
+    let shstrndx = read_u16(bytes, 0x3e)? as usize;
+
+    let header = |index: usize| -> Option<(u32, u32, &[u8])> {
+        let at = shoff + index * shentsize;
+        let name = read_u32(bytes, at)?;
+        let kind = read_u32(bytes, at + 4)?;
+        let offset = read_u64(bytes, at + 0x18)? as usize;
+        let size = read_u64(bytes, at + 0x20)? as usize;
+        //SHT_NOBITS sections such as .bss take no space in the file
+        let data = match kind {
+            8 => &[][..],
+            _ => bytes.get(offset..offset.checked_add(size)?)?,
+        };
+        Some((name, kind, data))
+    };
+    let (_, _, names) = header(shstrndx)?;
+
+    let mut sections = Vec::new();
+    for index in 1..shnum {
+        let (name, _, data) = header(index)?;
+        let name = names.get(name as usize..)?;
+        let end = name
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(name.len());
+        let name = String::from_utf8_lossy(&name[..end]).to_string();
+        unique(&mut sections, name, sea::hash64(data));
+    }
+    Some(sections)
+}
+
+//members of ar archives such as rlibs, with the sections of members that are ELF objects
+fn archive_sections(bytes: &[u8]) -> Option<Vec<(String, u64)>> {
+    if !bytes.starts_with(b"!<arch>\n") {
+        return None;
+    }
+    let mut sections = Vec::new();
+    let mut member_names = Vec::new();
+    let mut long_names: &[u8] = &[];
+    let mut at = 8;
+    while at + 60 <= bytes.len() {
+        let header = &bytes[at..at + 60];
+        let size: usize = str::parse(String::from_utf8_lossy(&header[48..58]).trim()).ok()?;
+        let data = bytes.get(at + 60..at + 60 + size)?;
+        let raw = String::from_utf8_lossy(&header[..16])
+            .trim_end()
+            .to_string();
+        at += 60 + size + size % 2;
+
+        let name = match raw.as_str() {
+            "/" | "/SYM64/" => continue,
+            "//" => {
+                long_names = data;
+                continue;
+            }
+            _ if raw.starts_with('/') => {
+                let start: usize = raw[1..].parse().ok()?;
+                let name = long_names.get(start..)?;
+                let end = name
+                    .iter()
+                    .position(|byte| *byte == b'\n')
+                    .unwrap_or(name.len());
+                String::from_utf8_lossy(&name[..end]).to_string()
+            }
+            _ => raw,
+        };
+        let name = name.trim_end_matches('/').to_string();
+        member_names.push(name.clone());
+        match elf_sections(data) {
+            Some(members) => {
+                for (section, hash) in members {
+                    unique(&mut sections, format!("{}:{}", name, section), hash);
+                }
+            }
+            None => unique(&mut sections, name, sea::hash64(data)),
+        }
+    }
+    unique(
+        &mut sections,
+        "(member names)".to_string(),
+        sea::hash64(member_names.join("\n").as_bytes()),
+    );
+    Some(sections)
+}
+
+pub fn get_sections(bytes: &[u8]) -> Option<Vec<(String, u64)>> {
+    elf_sections(bytes).or_else(|| archive_sections(bytes))
+}
+
+//sections whose content is not the same in every build, or that only some builds have
+pub fn differing_sections(builds: &[Vec<(String, u64)>]) -> Vec<String> {
+    let mut hashes: BTreeMap<&str, Vec<u64>> = BTreeMap::new();
+    for sections in builds.iter() {
+        for (name, hash) in sections.iter() {
+            hashes.entry(name).or_default().push(*hash);
+        }
+    }
+    //keep the section order of the first build
+    let mut names: Vec<&str> = Vec::new();
+    for sections in builds.iter() {
+        for (name, _) in sections.iter() {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+    }
+    names
+        .into_iter()
+        .filter(|name| {
+            let hashes = &hashes[name];
+            hashes.len() != builds.len() || hashes.iter().any(|hash| *hash != hashes[0])
+        })
+        .map(|name| name.to_string())
+        .collect()
+}
+
+//a fresh target directory per build, with the directory remapped so it cannot leak into the output
+fn build_once(toolkit: &str, target: &Path) -> Result<BuildHashes> {
+    match fs::remove_dir_all(target) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(reproduce_error(e)),
+        _ => (),
+    }
+    let mut rustflags = env::var("RUSTFLAGS").unwrap_or_default();
+    if !rustflags.is_empty() {
+        rustflags.push(' ');
+    }
+    rustflags.push_str(&format!("--remap-path-prefix={}=/target", target.display()));
+    let options = RunOptions {
+        env: vec![
+            (
+                "CARGO_TARGET_DIR".to_string(),
+                target.to_string_lossy().to_string(),
+            ),
+            ("RUSTFLAGS".to_string(), rustflags),
+            //incremental builds name their objects after a random session
+            ("CARGO_INCREMENTAL".to_string(), "0".to_string()),
+        ],
+        ..Default::default()
+    };
+
+    let mut command = toolkit_command(toolkit, "cargo");
+    command
+        .arg("test")
+        .arg("--no-run")
+        .arg("--message-format=json");
+    configure(&mut command, &options);
+    let output = command.output().map_err(reproduce_error)?;
+    if !output.status.success() {
+        return Err(reproduce_error(format!(
+            "cargo test --no-run failed in {}",
+            target.display()
+        )));
+    }
+
+    let mut hashes = BuildHashes::new();
+    for file in parse_artifact_files(&String::from_utf8_lossy(&output.stdout)) {
+        let bytes = fs::read(&file).map_err(reproduce_error)?;
+        let relative = file.strip_prefix(target).unwrap_or(&file);
+        hashes.insert(relative.to_string_lossy().to_string(), sea::hash64(&bytes));
+    }
+    Ok(hashes)
+}
+
+pub fn compare_builds(builds: &[BuildHashes]) -> Vec<NonReproducible> {
+    let paths: BTreeSet<&String> = builds.iter().flat_map(|build| build.keys()).collect();
+    paths
+        .into_iter()
+        .map(|path| NonReproducible {
+            path: path.clone(),
+            hashes: builds
+                .iter()
+                .map(|build| build.get(path).copied())
+                .collect(),
+            sections: Vec::new(),
+        })
+        .filter(|artifact| {
+            artifact
+                .hashes
+                .iter()
+                .any(|hash| *hash != artifact.hashes[0])
+        })
+        .collect()
+}
+
+pub fn check_reproducibility(toolkit: &str, builds: usize) -> Result<Reproducibility> {
+    if builds < 2 {
+        return Err(reproduce_error("at least two builds are needed to compare"));
+    }
+    let cwd = env::current_dir().map_err(reproduce_error)?;
+    let root = cwd
+        .join(referendum_dir())
+        .join("reproduce")
+        .join(toolkit_dir(toolkit));
+    //directory names of different lengths shift any path that is not remapped
+    let targets: Vec<PathBuf> = (0..builds)
+        .map(|build| root.join(format!("build-{}", "x".repeat(build + 1))))
+        .collect();
+
+    let mut hashes = Vec::new();
+    for target in targets.iter() {
+        hashes.push(build_once(toolkit, target)?);
+    }
+
+    let mut non_reproducible = compare_builds(&hashes);
+    for artifact in non_reproducible.iter_mut() {
+        let sections: Option<Vec<Vec<(String, u64)>>> = targets
+            .iter()
+            .map(|target| get_sections(&fs::read(target.join(&artifact.path)).ok()?))
+            .collect();
+        if let Some(sections) = sections {
+            artifact.sections = differing_sections(&sections);
+        }
+    }
+
+    Ok(Reproducibility {
+        toolkit: toolkit.to_string(),
+        builds,
+        artifacts: hashes.iter().map(|build| build.len()).max().unwrap_or(0),
+        non_reproducible,
+    })
+}
+
+pub fn get_reproducibility_results(reproducibility: &Reproducibility) -> String {
+    let mut builder = Builder::default();
+    builder.append("Reproducibility Results...\n");
+    builder.append(format!(
+        "built {} times with {}, {} artifacts\n",
+        reproducibility.builds, reproducibility.toolkit, reproducibility.artifacts
+    ));
+    if reproducibility.is_reproducible() {
+        builder.append("every artifact is reproducible\n");
+        return builder.string().unwrap();
+    }
+
+    builder.append(format!(
+        "{} non-reproducible artifacts:\n",
+        reproducibility.non_reproducible.len()
+    ));
+    for artifact in reproducibility.non_reproducible.iter() {
+        builder.append(format!("\t{}\n", artifact.path));
+        let missing: Vec<String> = artifact
+            .hashes
+            .iter()
+            .enumerate()
+            .filter(|(_, hash)| hash.is_none())
+            .map(|(build, _)| (build + 1).to_string())
+            .collect();
+        if !missing.is_empty() {
+            builder.append(format!("\t\tmissing from builds {}\n", missing.join(", ")));
+        }
+        if !artifact.sections.is_empty() {
+            builder.append(format!(
+                "\t\tdiffering sections: {}\n",
+                artifact.sections.join(", ")
+            ));
+        }
+    }
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a minimal ELF64 file with the given sections followed by .shstrtab
+    fn elf(sections: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut names = vec![0u8];
+        let mut data = Vec::new();
+        let mut headers = vec![[0u8; 64]];
+        let mut add = |name: &str, content: &[u8], names: &mut Vec<u8>| {
+            let mut header = [0u8; 64];
+            header[0..4].copy_from_slice(&(names.len() as u32).to_le_bytes());
+            header[4..8].copy_from_slice(&1u32.to_le_bytes());
+            header[0x18..0x20].copy_from_slice(&(64 + data.len() as u64).to_le_bytes());
+            header[0x20..0x28].copy_from_slice(&(content.len() as u64).to_le_bytes());
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+            data.extend_from_slice(content);
+            headers.push(header);
+        };
+        for (name, content) in sections {
+            add(name, content, &mut names);
+        }
+        let mut strtab = names.clone();
+        strtab.extend_from_slice(b".shstrtab\0");
+        add(".shstrtab", &strtab.clone(), &mut names);
+
+        let mut bytes = vec![0u8; 64];
+        bytes[0..4].copy_from_slice(b"\x7fELF");
+        bytes[4] = 2;
+        bytes[5] = 1;
+        bytes[0x28..0x30].copy_from_slice(&(64 + data.len() as u64).to_le_bytes());
+        bytes[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+        bytes[0x3c..0x3e].copy_from_slice(&(headers.len() as u16).to_le_bytes());
+        bytes[0x3e..0x40].copy_from_slice(&((headers.len() - 1) as u16).to_le_bytes());
+        bytes.extend_from_slice(&data);
+        for header in headers {
+            bytes.extend_from_slice(&header);
+        }
+        bytes
+    }
+
+    fn names(sections: &[(String, u64)]) -> Vec<&str> {
+        sections.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn elf_sections_are_read() {
+        let sections =
+            get_sections(&elf(&[(".text", b"\x90\x90"), (".comment", b"rustc")])).unwrap();
+        assert_eq!(names(&sections), vec![".text", ".comment", ".shstrtab"]);
+        assert_eq!(sections[0].1, sea::hash64(b"\x90\x90"));
+    }
+
+    #[test]
+    fn archive_members_are_read() {
+        let object = elf(&[(".text", b"\x90")]);
+        let mut archive = b"!<arch>\n".to_vec();
+        for (name, data) in [
+            ("lib.rmeta/", &b"meta"[..]),
+            ("a.cgu.x.rcgu.o/", &object[..]),
+        ] {
+            archive.extend_from_slice(
+                format!("{:<16}{:<32}{:<10}`\n", name, "0", data.len()).as_bytes(),
+            );
+            archive.extend_from_slice(data);
+            if data.len() % 2 == 1 {
+                archive.push(b'\n');
+            }
+        }
+        let sections = get_sections(&archive).unwrap();
+        assert_eq!(
+            names(&sections),
+            vec![
+                "lib.rmeta",
+                "a.cgu.x.rcgu.o:.text",
+                "a.cgu.x.rcgu.o:.shstrtab",
+                "(member names)"
+            ]
+        );
+    }
+
+    #[test]
+    fn other_files_have_no_sections() {
+        assert!(get_sections(b"#!/bin/sh\n").is_none());
+    }
+
+    #[test]
+    fn differing_sections_include_missing() {
+        let first = vec![(".text".to_string(), 1), (".data".to_string(), 2)];
+        let second = vec![
+            (".text".to_string(), 1),
+            (".data".to_string(), 3),
+            (".note".to_string(), 4),
+        ];
+        assert_eq!(differing_sections(&[first, second]), vec![".data", ".note"]);
+    }
+
+    #[test]
+    fn builds_are_compared_per_artifact() {
+        let build = |entries: &[(&str, u64)]| -> BuildHashes {
+            entries
+                .iter()
+                .map(|(path, hash)| (path.to_string(), *hash))
+                .collect()
+        };
+        let builds = vec![
+            build(&[("debug/deps/a", 1), ("debug/deps/b", 2)]),
+            build(&[
+                ("debug/deps/a", 1),
+                ("debug/deps/b", 3),
+                ("debug/deps/c", 4),
+            ]),
+        ];
+        let paths: Vec<String> = compare_builds(&builds)
+            .into_iter()
+            .map(|artifact| artifact.path)
+            .collect();
+        assert_eq!(paths, vec!["debug/deps/b", "debug/deps/c"]);
+    }
+}
@@ -0,0 +1,502 @@
+use crate::build::configure;
+use crate::{hash_outcome, parse_tests, parse_toolkit, Toolkit};
+use crate::{ReferendumError, Result, RunOptions, Test};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::str;
+
+//how a voter executes tests, implement it to vote from somewhere referendum does not know about
+pub trait Runner {
+    //compiles the tests without running them
+    fn build(&self, options: &RunOptions) -> Result<()>;
+    fn list_tests(&self, options: &RunOptions) -> Result<Vec<String>>;
+    //runs the tests and returns the raw output for collect_outcomes
+    fn run_tests(&self, options: &RunOptions) -> Result<String>;
+    fn collect_outcomes(&self, voter: &str, output: &str) -> Result<Vec<Test>>;
+
+    //a toolkit that cannot build the tests has no vote, so the build has to succeed before
+    //anything runs, failing tests are still a vote once they ran
+    fn get_tests(&self, voter: &str, options: &RunOptions) -> Result<Vec<Test>> {
+        self.build(options)?;
+        self.collect_outcomes(voter, &self.run_tests(options)?)
+    }
+}
+
+//which runner plain toolkits vote with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RunnerKind {
+    #[default]
+    Cargo,
+    Nextest,
+}
+
+//libtest's `--list --format=terse` prints `name: test` for every test
+pub fn parse_test_list(list: &str) -> Vec<String> {
+    list.lines()
+        .filter_map(|line| line.strip_suffix(": test"))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+//a toolkit that cannot build the tests has no vote, failing tests are still one once libtest reported them
+pub(crate) fn check_test_run(success: bool, stdout: &str, stderr: &str) -> Result<()> {
+    if success {
+        return Ok(());
+    }
+    if let Some(error) = stderr
+        .lines()
+        .find(|line| line.starts_with("error: could not compile"))
+    {
+        return Err(ReferendumError::BuildFailure(
+            error.trim_start_matches("error: ").to_string(),
+        ));
+    }
+    match stdout.contains("test result:") {
+        true => Ok(()),
+        //want to add information about the error here too
+        false => Err(ReferendumError::TestRunFailure()),
+    }
+}
+
+fn output_of(mut command: Command, options: &RunOptions) -> Result<Output> {
+    configure(&mut command, options);
+    command
+        .output()
+        .map_err(|_| ReferendumError::TestRunFailure())
+}
+
+fn build_with(toolkit: &Toolkit, options: &RunOptions) -> Result<()> {
+    let mut command = toolkit.command("cargo");
+    command.arg("test").arg("--no-run");
+    match output_of(command, options)?.status.success() {
+        true => Ok(()),
+        false => Err(ReferendumError::BuildFailure(
+            "cargo test --no-run failed".to_string(),
+        )),
+    }
+}
+
+fn list_with(toolkit: &Toolkit, options: &RunOptions) -> Result<Vec<String>> {
+    let mut command = toolkit.command("cargo");
+    command.arg("test");
+    if let Some(filter) = &options.filter {
+        command.arg(filter);
+    }
+    command.arg("--").arg("--list").arg("--format=terse");
+    let output = output_of(command, options)?;
+    Ok(parse_test_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+//every test binary runs even when an earlier one has failing tests, those failures are votes too
+pub(crate) fn cargo_test_args(filter: Option<&str>) -> Vec<String> {
+    let mut args = vec!["--no-fail-fast".to_string()];
+    args.extend(filter.map(|filter| filter.to_string()));
+    args.extend(
+        ["--", "--test-threads=1", "--show-output"]
+            .iter()
+            .map(|arg| arg.to_string()),
+    );
+    args
+}
+
+fn run_with(toolkit: &Toolkit, options: &RunOptions) -> Result<String> {
+    let mut command = toolkit.command("cargo");
+    command
+        .arg("test")
+        .args(cargo_test_args(options.filter.as_deref()));
+    let output = output_of(command, options)?;
+
+    let stdout = match str::from_utf8(&output.stdout) {
+        Ok(v) => v.to_string(),
+        Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
+    };
+
+    check_test_run(
+        output.status.success(),
+        &stdout,
+        &String::from_utf8_lossy(&output.stderr),
+    )?;
+    Ok(stdout)
+}
+
+//cargo test through `rustup run`
+#[derive(Debug, Clone)]
+pub struct RustupRunner {
+    pub toolchain: String,
+}
+
+impl Runner for RustupRunner {
+    fn build(&self, options: &RunOptions) -> Result<()> {
+        build_with(&Toolkit::Rustup(self.toolchain.clone()), options)
+    }
+
+    fn list_tests(&self, options: &RunOptions) -> Result<Vec<String>> {
+        list_with(&Toolkit::Rustup(self.toolchain.clone()), options)
+    }
+
+    fn run_tests(&self, options: &RunOptions) -> Result<String> {
+        run_with(&Toolkit::Rustup(self.toolchain.clone()), options)
+    }
+
+    fn collect_outcomes(&self, voter: &str, output: &str) -> Result<Vec<Test>> {
+        parse_tests(voter, output)
+    }
+}
+
+//cargo test with a rustc, and optionally a cargo, that rustup does not manage
+#[derive(Debug, Clone)]
+pub struct PathRunner {
+    pub rustc: PathBuf,
+    pub cargo: Option<PathBuf>,
+}
+
+impl PathRunner {
+    fn toolkit(&self) -> Toolkit {
+        Toolkit::Paths {
+            rustc: self.rustc.clone(),
+            cargo: self.cargo.clone(),
+        }
+    }
+}
+
+impl Runner for PathRunner {
+    fn build(&self, options: &RunOptions) -> Result<()> {
+        build_with(&self.toolkit(), options)
+    }
+
+    fn list_tests(&self, options: &RunOptions) -> Result<Vec<String>> {
+        list_with(&self.toolkit(), options)
+    }
+
+    fn run_tests(&self, options: &RunOptions) -> Result<String> {
+        run_with(&self.toolkit(), options)
+    }
+
+    fn collect_outcomes(&self, voter: &str, output: &str) -> Result<Vec<Test>> {
+        parse_tests(voter, output)
+    }
+}
+
+//the runner for a toolkit as written on the command line
+pub fn toolkit_runner(toolkit: &str) -> Box<dyn Runner> {
+    match parse_toolkit(toolkit) {
+        Toolkit::Rustup(toolchain) => Box::new(RustupRunner { toolchain }),
+        Toolkit::Paths { rustc, cargo } => Box::new(PathRunner { rustc, cargo }),
+    }
+}
+
+pub fn select_runner(toolkit: &str, kind: RunnerKind) -> Box<dyn Runner> {
+    match kind {
+        RunnerKind::Cargo => toolkit_runner(toolkit),
+        RunnerKind::Nextest => Box::new(NextestRunner {
+            toolkit: toolkit.to_string(),
+        }),
+    }
+}
+
+//cargo nextest with libtest-compatible json output, which nextest still gates behind an environment variable
+#[derive(Debug, Clone)]
+pub struct NextestRunner {
+    pub toolkit: String,
+}
+
+//nextest names tests `binary-id$test name`
+fn nextest_test_name(name: &str) -> &str {
+    name.split_once('$').map(|(_, test)| test).unwrap_or(name)
+}
+
+pub fn parse_nextest_list(json: &str) -> Vec<String> {
+    let list: Value = serde_json::from_str(json).unwrap_or(Value::Null);
+    let mut names = Vec::new();
+    if let Some(suites) = list["rust-suites"].as_object() {
+        for suite in suites.values() {
+            if let Some(cases) = suite["testcases"].as_object() {
+                names.extend(cases.keys().cloned());
+            }
+        }
+    }
+    names
+}
+
+pub fn parse_libtest_json(voter: &str, output: &str) -> Vec<Test> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|event| event["type"] == "test")
+        .filter_map(|event| {
+            let result = match event["event"].as_str()? {
+                "ok" => true,
+                "failed" | "timeout" => false,
+                _ => return None,
+            };
+            let output = event["stdout"]
+                .as_str()
+                .unwrap_or("")
+                .trim_end()
+                .to_string();
+            let duration = event["exec_time"].as_f64();
+            Some(Test {
+                name: nextest_test_name(event["name"].as_str()?).to_string(),
+                toolkit: voter.to_string(),
+                result,
+                hash: hash_outcome(result, &output),
+                output,
+                duration,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+impl NextestRunner {
+    pub fn preflight(&self) -> Result<()> {
+        let installed = parse_toolkit(&self.toolkit)
+            .command("cargo")
+            .arg("nextest")
+            .arg("--version")
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false);
+        match installed {
+            true => Ok(()),
+            false => Err(ReferendumError::VoterUnavailable(
+                self.toolkit.clone(),
+                "cargo-nextest is not installed, run `cargo install cargo-nextest`".to_string(),
+            )),
+        }
+    }
+
+    fn nextest(&self, subcommand: &str, options: &RunOptions) -> Command {
+        let mut command = parse_toolkit(&self.toolkit).command("cargo");
+        command.arg("nextest").arg(subcommand);
+        if let Some(filter) = &options.filter {
+            command.arg(filter);
+        }
+        command
+    }
+}
+
+impl Runner for NextestRunner {
+    fn build(&self, options: &RunOptions) -> Result<()> {
+        build_with(&parse_toolkit(&self.toolkit), options)
+    }
+
+    fn list_tests(&self, options: &RunOptions) -> Result<Vec<String>> {
+        let mut command = self.nextest("list", options);
+        command.arg("--message-format").arg("json");
+        let output = output_of(command, options)?;
+        if !output.status.success() {
+            return Err(ReferendumError::TestRunFailure());
+        }
+        Ok(parse_nextest_list(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    fn run_tests(&self, options: &RunOptions) -> Result<String> {
+        let mut command = self.nextest("run", options);
+        command
+            .arg("--no-fail-fast")
+            .arg("--message-format")
+            .arg("libtest-json")
+            .env("NEXTEST_EXPERIMENTAL_LIBTEST_JSON", "1");
+        let output = output_of(command, options)?;
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        if !output.status.success() && !stdout.contains("\"type\":\"suite\"") {
+            return Err(ReferendumError::TestRunFailure());
+        }
+        Ok(stdout)
+    }
+
+    fn collect_outcomes(&self, voter: &str, output: &str) -> Result<Vec<Test>> {
+        Ok(parse_libtest_json(voter, output))
+    }
+}
+
+//a runner made of command lines, `{toolkit}` and `{filter}` are replaced in each argument
+//and the run command must print libtest's output, e.g. `chroot /srv/jail cargo +{toolkit} test {filter} -- --show-output`
+#[derive(Debug, Clone)]
+pub struct CommandRunner {
+    pub toolkit: String,
+    pub build: Option<String>,
+    pub list: Option<String>,
+    pub run: String,
+}
+
+pub fn expand_template(template: &str, toolkit: &str, filter: Option<&str>) -> Vec<String> {
+    template
+        .split_whitespace()
+        .filter(|arg| *arg != "{filter}" || filter.is_some())
+        .map(|arg| {
+            arg.replace("{toolkit}", toolkit)
+                .replace("{filter}", filter.unwrap_or(""))
+        })
+        .collect()
+}
+
+impl CommandRunner {
+    fn command(&self, template: &str, options: &RunOptions) -> Result<Output> {
+        let args = expand_template(template, &self.toolkit, options.filter.as_deref());
+        let (program, args) = args
+            .split_first()
+            .ok_or(ReferendumError::TestRunFailure())?;
+        let mut command = Command::new(program);
+        command.args(args);
+        output_of(command, options)
+    }
+}
+
+impl Runner for CommandRunner {
+    fn build(&self, options: &RunOptions) -> Result<()> {
+        let template = match &self.build {
+            Some(template) => template,
+            None => return Ok(()),
+        };
+        match self.command(template, options)?.status.success() {
+            true => Ok(()),
+            false => Err(ReferendumError::BuildFailure(format!(
+                "{} failed",
+                template
+            ))),
+        }
+    }
+
+    fn list_tests(&self, options: &RunOptions) -> Result<Vec<String>> {
+        match &self.list {
+            Some(template) => Ok(parse_test_list(&String::from_utf8_lossy(
+                &self.command(template, options)?.stdout,
+            ))),
+            //listing through a full run would cost as much as the vote itself
+            None => Err(ReferendumError::RunnerFailure(
+                "the command runner has no list template".to_string(),
+            )),
+        }
+    }
+
+    fn run_tests(&self, options: &RunOptions) -> Result<String> {
+        let output = self.command(&self.run, options)?;
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        check_test_run(
+            output.status.success(),
+            &stdout,
+            &String::from_utf8_lossy(&output.stderr),
+        )?;
+        Ok(stdout)
+    }
+
+    fn collect_outcomes(&self, voter: &str, output: &str) -> Result<Vec<Test>> {
+        parse_tests(voter, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_skips_benches() {
+        assert_eq!(
+            parse_test_list("tests::adds: test\nbenches::sum: bench\n"),
+            vec!["tests::adds".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_runs_do_not_stop_at_the_first_failing_binary() {
+        assert_eq!(
+            cargo_test_args(Some("tests::a")),
+            vec![
+                "--no-fail-fast",
+                "tests::a",
+                "--",
+                "--test-threads=1",
+                "--show-output"
+            ]
+        );
+        assert_eq!(cargo_test_args(None)[0], "--no-fail-fast");
+    }
+
+    #[test]
+    fn failing_tests_vote_but_build_failures_do_not() {
+        let failed = "test tests::fails ... FAILED\n\ntest result: FAILED. 0 passed; 1 failed";
+        assert!(check_test_run(false, failed, "error: test failed, to rerun pass `--lib`").is_ok());
+        //one target built and ran before another failed to compile
+        assert!(matches!(
+            check_test_run(
+                false,
+                failed,
+                "error[E0425]: cannot find value `x` in this scope\nerror: could not compile `sample` (test \"it\") due to 1 previous error"
+            ),
+            Err(ReferendumError::BuildFailure(_))
+        ));
+        assert!(matches!(
+            check_test_run(false, "", "error: no such command: `frobnicate`"),
+            Err(ReferendumError::TestRunFailure())
+        ));
+        assert!(check_test_run(true, "test result: ok.", "").is_ok());
+    }
+
+    #[test]
+    fn command_runners_list_only_with_a_template() {
+        let runner = CommandRunner {
+            toolkit: "nightly".to_string(),
+            build: None,
+            list: None,
+            run: "cargo +{toolkit} test {filter} -- --show-output".to_string(),
+        };
+        assert!(matches!(
+            runner.list_tests(&RunOptions::default()),
+            Err(ReferendumError::RunnerFailure(_))
+        ));
+    }
+
+    #[test]
+    fn templates_drop_a_missing_filter() {
+        assert_eq!(
+            expand_template(
+                "chroot /srv/jail cargo +{toolkit} test {filter} -- --show-output",
+                "nightly",
+                None
+            ),
+            vec![
+                "chroot",
+                "/srv/jail",
+                "cargo",
+                "+nightly",
+                "test",
+                "--",
+                "--show-output"
+            ]
+        );
+        assert_eq!(
+            expand_template("run-tests --only={filter}", "stable", Some("tests::a")),
+            vec!["run-tests", "--only=tests::a"]
+        );
+    }
+
+    #[test]
+    fn nextest_list_names_every_testcase() {
+        let json = r#"{"test-count":2,"rust-suites":{"sample":{"binary-id":"sample","testcases":{"tests::adds":{"ignored":false},"tests::fails":{"ignored":false}}}}}"#;
+        assert_eq!(
+            parse_nextest_list(json),
+            vec!["tests::adds".to_string(), "tests::fails".to_string()]
+        );
+    }
+
+    #[test]
+    fn libtest_json_events_become_votes() {
+        let output = r#"{"type":"suite","event":"started","test_count":2}
+{"type":"test","event":"started","name":"sample$tests::adds"}
+{"type":"test","event":"ok","name":"sample$tests::adds","exec_time":0.001}
+{"type":"test","event":"started","name":"sample$tests::fails"}
+{"type":"test","event":"failed","name":"sample$tests::fails","exec_time":0.002,"stdout":"thread 'tests::fails' panicked\n"}
+{"type":"suite","event":"failed","passed":1,"failed":1,"ignored":0,"measured":0,"filtered_out":0,"exec_time":0.003}"#;
+        let tests = parse_libtest_json("nextest", output);
+        assert_eq!(tests.len(), 2);
+        assert_eq!(tests[0].name, "tests::adds");
+        assert!(tests[0].result);
+        assert_eq!(tests[1].name, "tests::fails");
+        assert!(!tests[1].result);
+        assert_eq!(tests[1].output, "thread 'tests::fails' panicked");
+        assert_eq!(tests[1].duration, Some(0.002));
+    }
+}
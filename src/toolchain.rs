@@ -0,0 +1,148 @@
+use crate::voter::{parse_voter, preflight};
+use crate::{check_toolkit, toolkit_command, ReferendumError, Result, RunOptions};
+use serde::{Deserialize, Serialize};
+use std::str;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Toolchain {
+    pub name: String,
+    pub release: String,
+    pub commit_hash: Option<String>,
+    pub commit_date: Option<String>,
+    pub host: String,
+    pub llvm_version: Option<String>,
+    pub cargo_version: String,
+}
+
+impl Toolchain {
+    pub fn describe(&self) -> String {
+        let mut parts = vec![self.release.clone()];
+        if let Some(llvm) = &self.llvm_version {
+            parts.push(format!("LLVM {}", llvm));
+        }
+        parts.push(self.host.clone());
+        parts.push(self.cargo_version.clone());
+        parts.join(", ")
+    }
+}
+
+//"unknown" is what locally built compilers report for missing fields
+fn get_field(rustc_version: &str, key: &str) -> Option<String> {
+    rustc_version
+        .lines()
+        .filter_map(|line| line.strip_prefix(key))
+        .filter_map(|rest| rest.strip_prefix(':'))
+        .map(|value| value.trim().to_string())
+        .find(|value| !value.is_empty() && value != "unknown")
+}
+
+pub fn parse_toolchain(name: &str, rustc_version: &str, cargo_version: &str) -> Toolchain {
+    Toolchain {
+        name: name.to_string(),
+        release: rustc_version
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_string(),
+        commit_hash: get_field(rustc_version, "commit-hash"),
+        commit_date: get_field(rustc_version, "commit-date"),
+        host: get_field(rustc_version, "host").unwrap_or_default(),
+        llvm_version: get_field(rustc_version, "LLVM version"),
+        cargo_version: cargo_version.trim().to_string(),
+    }
+}
+
+fn run_tool(toolkit: &str, tool: &str, flag: &str) -> Result<String> {
+    let output = toolkit_command(toolkit, tool)
+        .arg(flag)
+        .output()
+        .map_err(|_| ReferendumError::ToolchainNotFound(toolkit.to_string()))?;
+
+    if !output.status.success() {
+        return Err(ReferendumError::ToolchainNotFound(toolkit.to_string()));
+    }
+
+    match str::from_utf8(&output.stdout) {
+        Ok(v) => Ok(v.to_string()),
+        Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
+    }
+}
+
+pub fn get_toolchain(spec: &str) -> Result<Toolchain> {
+    get_toolchain_with(spec, &RunOptions::default())
+}
+
+//identifies the toolkit behind a voter, and checks the voter can run with these options
+pub fn get_toolchain_with(spec: &str, options: &RunOptions) -> Result<Toolchain> {
+    let voter = parse_voter(spec);
+    check_toolkit(&voter.toolkit)?;
+    let rustc_version = run_tool(&voter.toolkit, "rustc", "-vV")?;
+    let cargo_version = run_tool(&voter.toolkit, "cargo", "-V")?;
+    preflight(&voter, options)?;
+    Ok(parse_toolchain(spec, &rustc_version, &cargo_version))
+}
+
+pub fn get_toolchains(toolkits: &[&str]) -> Result<Vec<Toolchain>> {
+    get_toolchains_with(toolkits, &RunOptions::default())
+}
+
+pub fn get_toolchains_with(toolkits: &[&str], options: &RunOptions) -> Result<Vec<Toolchain>> {
+    toolkits
+        .iter()
+        .map(|kit| get_toolchain_with(kit, options))
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) fn sample_toolchain(name: &str) -> Toolchain {
+    parse_toolchain(
+        name,
+        "rustc 1.55.0-nightly (b41936b92 2021-06-02)
+binary: rustc
+commit-hash: b41936b92cd8463020207cb2f62a4247942ef2e4
+commit-date: 2021-06-02
+host: x86_64-apple-darwin
+release: 1.55.0-nightly
+LLVM version: 12.0.1",
+        "cargo 1.54.0-nightly (0cecbd673 2021-06-01)",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rustup_toolchain() {
+        let toolchain = sample_toolchain("nightly");
+        assert_eq!(
+            toolchain.release,
+            "rustc 1.55.0-nightly (b41936b92 2021-06-02)"
+        );
+        assert_eq!(
+            toolchain.commit_hash.as_deref(),
+            Some("b41936b92cd8463020207cb2f62a4247942ef2e4")
+        );
+        assert_eq!(toolchain.commit_date.as_deref(), Some("2021-06-02"));
+        assert_eq!(toolchain.host, "x86_64-apple-darwin");
+        assert_eq!(toolchain.llvm_version.as_deref(), Some("12.0.1"));
+        assert_eq!(
+            toolchain.cargo_version,
+            "cargo 1.54.0-nightly (0cecbd673 2021-06-01)"
+        );
+    }
+
+    #[test]
+    fn parse_local_build_toolchain() {
+        let toolchain = parse_toolchain(
+            "stage1",
+            "rustc 1.56.0-dev\nbinary: rustc\ncommit-hash: unknown\ncommit-date: unknown\nhost: x86_64-unknown-linux-gnu\nrelease: 1.56.0-dev\n",
+            "cargo 1.56.0",
+        );
+        assert_eq!(toolchain.commit_hash, None);
+        assert_eq!(toolchain.commit_date, None);
+        assert_eq!(toolchain.llvm_version, None);
+        assert_eq!(toolchain.host, "x86_64-unknown-linux-gnu");
+    }
+}
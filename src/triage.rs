@@ -0,0 +1,194 @@
+use crate::toolchain::get_toolchain;
+use crate::voter::sanitizer::{self, Sanitizer};
+use crate::voter::{miri, parse_voter};
+use crate::{referendum_dir, Finding, Result, RunOptions, VoteResult};
+use std::collections::BTreeSet;
+use string_builder::Builder;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriageVerdict {
+    LikelyUb,
+    NoUbDetected,
+    Unchecked,
+}
+
+impl TriageVerdict {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            TriageVerdict::LikelyUb => "likely UB in user code",
+            TriageVerdict::NoUbDetected => "no UB detected, possible compiler bug",
+            TriageVerdict::Unchecked => "not checked, no UB checker could run",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Triage {
+    pub test: String,
+    //checkers that ran the test to completion
+    pub checked: Vec<String>,
+    //checkers that could not run, with the reason
+    pub skipped: Vec<(String, String)>,
+    pub findings: Vec<Finding>,
+}
+
+impl Triage {
+    pub fn verdict(&self) -> TriageVerdict {
+        match (self.findings.is_empty(), self.checked.is_empty()) {
+            (false, _) => TriageVerdict::LikelyUb,
+            (true, false) => TriageVerdict::NoUbDetected,
+            (true, true) => TriageVerdict::Unchecked,
+        }
+    }
+}
+
+fn miri_options(filter: &str) -> RunOptions {
+    RunOptions {
+        filter: Some(filter.to_string()),
+        env: vec![(
+            "CARGO_TARGET_DIR".to_string(),
+            referendum_dir()
+                .join("triage")
+                .join("miri")
+                .to_string_lossy()
+                .to_string(),
+        )],
+        ..Default::default()
+    }
+}
+
+fn miri_findings(toolkit: &str, test: &str) -> std::result::Result<Vec<Finding>, String> {
+    let voter = parse_voter(&format!("miri:{}", toolkit));
+    miri::preflight(&voter).map_err(|e| e.to_string())?;
+    let tests = miri::get_miri_tests(&voter, &miri_options(test)).map_err(|e| e.to_string())?;
+    Ok(tests
+        .into_iter()
+        .filter(|run| run.name == test)
+        .flat_map(|run| run.findings)
+        .collect())
+}
+
+fn asan_findings(toolkit: &str, test: &str) -> std::result::Result<Vec<Finding>, String> {
+    let voter = parse_voter(&format!("asan:{}", toolkit));
+    sanitizer::preflight(&voter, Sanitizer::Address).map_err(|e| e.to_string())?;
+    let tests = sanitizer::get_sanitizer_tests(
+        &voter,
+        Sanitizer::Address,
+        &RunOptions {
+            filter: Some(test.to_string()),
+            ..Default::default()
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(tests
+        .into_iter()
+        .filter(|run| run.name == test)
+        .flat_map(|run| run.findings)
+        .collect())
+}
+
+fn record(triage: &mut Triage, checker: &str, findings: std::result::Result<Vec<Finding>, String>) {
+    match findings {
+        Ok(findings) => {
+            triage.checked.push(checker.to_string());
+            triage.findings.extend(findings);
+        }
+        Err(reason) => triage.skipped.push((checker.to_string(), reason)),
+    }
+}
+
+//reruns every dissenting test under miri and AddressSanitizer with a nightly toolkit
+pub fn triage_dissents(votes: &VoteResult, toolkit: &str) -> Result<Vec<Triage>> {
+    get_toolchain(toolkit)?;
+    let names: BTreeSet<&str> = votes
+        .non_matches
+        .iter()
+        .map(|test| test.name.as_str())
+        .collect();
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            let mut triage = Triage {
+                test: name.to_string(),
+                checked: Vec::new(),
+                skipped: Vec::new(),
+                findings: Vec::new(),
+            };
+            record(&mut triage, "miri", miri_findings(toolkit, name));
+            record(&mut triage, "asan", asan_findings(toolkit, name));
+            triage
+        })
+        .collect())
+}
+
+pub fn describe_triage(triage: &Triage) -> String {
+    let mut line = format!("{} ... {}", triage.test, triage.verdict().describe());
+    let details: Vec<String> = match triage.verdict() {
+        TriageVerdict::LikelyUb => triage
+            .findings
+            .iter()
+            .map(|finding| format!("{}: {}: {}", finding.tool, finding.kind, finding.message))
+            .collect(),
+        _ => triage
+            .checked
+            .iter()
+            .map(|checker| format!("{} clean", checker))
+            .chain(
+                triage
+                    .skipped
+                    .iter()
+                    .map(|(checker, reason)| format!("{} skipped: {}", checker, reason)),
+            )
+            .collect(),
+    };
+    if !details.is_empty() {
+        line.push_str(&format!(" ({})", details.join("; ")));
+    }
+    line
+}
+
+pub fn get_triage_results(triages: &[Triage]) -> String {
+    let mut builder = Builder::default();
+    builder.append("Dissent Triage Results...\n");
+    for triage in triages.iter() {
+        builder.append(describe_triage(triage));
+        builder.append("\n");
+    }
+    builder.string().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triage(checked: &[&str], findings: Vec<Finding>) -> Triage {
+        Triage {
+            test: "tests::a".to_string(),
+            checked: checked.iter().map(|checker| checker.to_string()).collect(),
+            skipped: vec![("miri".to_string(), "not installed".to_string())],
+            findings,
+        }
+    }
+
+    #[test]
+    fn verdict_follows_findings_then_checks() {
+        let ub = Finding {
+            tool: "asan".to_string(),
+            kind: "heap-use-after-free".to_string(),
+            message: "on address 0x6020".to_string(),
+        };
+        assert_eq!(
+            triage(&["asan"], vec![ub]).verdict(),
+            TriageVerdict::LikelyUb
+        );
+        assert_eq!(
+            triage(&["asan"], Vec::new()).verdict(),
+            TriageVerdict::NoUbDetected
+        );
+        assert_eq!(triage(&[], Vec::new()).verdict(), TriageVerdict::Unchecked);
+        assert_eq!(
+            describe_triage(&triage(&["asan"], Vec::new())),
+            "tests::a ... no UB detected, possible compiler bug (asan clean; miri skipped: not installed)"
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use crate::runner::{select_runner, NextestRunner, RunnerKind};
+use crate::{ReferendumError, Result, RunOptions, Test};
+use sanitizer::Sanitizer;
+
+pub mod backend;
+pub mod cross;
+pub mod isolated;
+pub mod miri;
+pub mod sanitizer;
+pub mod valgrind;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoterKind {
+    Rustup,
+    Miri,
+    Sanitizer(Sanitizer),
+    Valgrind,
+    //the -Zcodegen-backend value, a backend name or the path to one
+    Backend(String),
+}
+
+//a voter runs the tests with a toolkit in a particular way, written `kind:toolkit@target=runner` on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Voter {
+    pub name: String,
+    pub toolkit: String,
+    pub kind: VoterKind,
+    pub target: Option<String>,
+    pub runner: Option<String>,
+}
+
+pub fn parse_voter(spec: &str) -> Voter {
+    //the runner is a command line of its own, so the target is split off before the kind
+    let (toolkit_spec, target, runner) = match spec.split_once('@') {
+        Some((toolkit_spec, cross)) => match cross.split_once('=') {
+            Some((target, runner)) => (toolkit_spec, Some(target), Some(runner)),
+            None => (toolkit_spec, Some(cross), None),
+        },
+        None => (spec, None, None),
+    };
+    let (kind, toolkit) = match toolkit_spec.split_once(':') {
+        Some(("miri", toolkit)) => (VoterKind::Miri, toolkit),
+        Some(("valgrind", toolkit)) => (VoterKind::Valgrind, toolkit),
+        Some(("cranelift", toolkit)) => (VoterKind::Backend("cranelift".to_string()), toolkit),
+        Some((prefix, toolkit)) if prefix.starts_with("gcc=") => (
+            VoterKind::Backend(prefix.trim_start_matches("gcc=").to_string()),
+            toolkit,
+        ),
+        Some((prefix, toolkit)) => match Sanitizer::from_prefix(prefix) {
+            Some(sanitizer) => (VoterKind::Sanitizer(sanitizer), toolkit),
+            None => (VoterKind::Rustup, toolkit_spec),
+        },
+        None => (VoterKind::Rustup, toolkit_spec),
+    };
+    Voter {
+        name: spec.to_string(),
+        toolkit: toolkit.to_string(),
+        kind,
+        target: target.map(|target| target.to_string()),
+        runner: runner.map(|runner| runner.to_string()),
+    }
+}
+
+//the toolkit of a plain voter, for commands that build the crate themselves and cannot reproduce how other voters build
+pub fn plain_toolkit(spec: &str, command: &str) -> Result<String> {
+    let voter = parse_voter(spec);
+    match voter.kind == VoterKind::Rustup && voter.target.is_none() {
+        true => Ok(voter.toolkit),
+        false => Err(ReferendumError::VoterUnavailable(
+            voter.name,
+            format!("{} only supports plain toolkits", command),
+        )),
+    }
+}
+
+//plain and backend voters run the tests through the selected runner
+fn runner_preflight(voter: &Voter, options: &RunOptions) -> Result<()> {
+    match options.runner {
+        RunnerKind::Cargo => Ok(()),
+        RunnerKind::Nextest => NextestRunner {
+            toolkit: voter.toolkit.clone(),
+        }
+        .preflight(),
+    }
+}
+
+//checks that the tools the voter needs beyond rustc and cargo are installed
+pub fn preflight(voter: &Voter, options: &RunOptions) -> Result<()> {
+    match &voter.kind {
+        VoterKind::Rustup if voter.target.is_some() => cross::preflight(voter),
+        VoterKind::Rustup => runner_preflight(voter, options),
+        _ if voter.target.is_some() => Err(ReferendumError::VoterUnavailable(
+            voter.name.clone(),
+            "only plain toolkits can vote for another target".to_string(),
+        )),
+        VoterKind::Miri => miri::preflight(voter),
+        VoterKind::Sanitizer(kind) => sanitizer::preflight(voter, *kind),
+        VoterKind::Valgrind => valgrind::preflight(voter),
+        VoterKind::Backend(backend) => {
+            backend::preflight(voter, backend)?;
+            runner_preflight(voter, options)
+        }
+    }
+}
+
+pub fn get_voter_tests(voter: &Voter, options: &RunOptions) -> Result<Vec<Test>> {
+    match &voter.kind {
+        VoterKind::Rustup if voter.target.is_some() => cross::get_cross_tests(voter, options),
+        VoterKind::Rustup => {
+            select_runner(&voter.toolkit, options.runner).get_tests(&voter.name, options)
+        }
+        VoterKind::Miri => miri::get_miri_tests(voter, options),
+        VoterKind::Sanitizer(kind) => sanitizer::get_sanitizer_tests(voter, *kind, options),
+        VoterKind::Valgrind => valgrind::get_valgrind_tests(voter, options),
+        VoterKind::Backend(backend) => backend::get_backend_tests(voter, backend, options),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_toolkits_are_rustup_voters() {
+        let voter = parse_voter("nightly-2021-06-02");
+        assert_eq!(voter.kind, VoterKind::Rustup);
+        assert_eq!(voter.toolkit, "nightly-2021-06-02");
+    }
+
+    #[test]
+    fn cross_voters_keep_the_runner_whole() {
+        let voter =
+            parse_voter("stable@aarch64-unknown-linux-gnu=qemu-aarch64 -L /usr/aarch64-linux-gnu");
+        assert_eq!(voter.kind, VoterKind::Rustup);
+        assert_eq!(voter.toolkit, "stable");
+        assert_eq!(voter.target.as_deref(), Some("aarch64-unknown-linux-gnu"));
+        assert_eq!(
+            voter.runner.as_deref(),
+            Some("qemu-aarch64 -L /usr/aarch64-linux-gnu")
+        );
+        assert_eq!(parse_voter("nightly@i686-unknown-linux-gnu").runner, None);
+    }
+
+    #[test]
+    fn only_plain_voters_have_a_plain_toolkit() {
+        assert_eq!(
+            plain_toolkit("nightly", "artifact-diff").unwrap(),
+            "nightly"
+        );
+        assert!(plain_toolkit("miri:nightly", "artifact-diff").is_err());
+        assert!(plain_toolkit(
+            "stable@aarch64-unknown-linux-gnu=/usr/bin/qemu",
+            "artifact-diff"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn miri_voters_name_their_toolkit() {
+        let voter = parse_voter("miri:nightly");
+        assert_eq!(voter.kind, VoterKind::Miri);
+        assert_eq!(voter.toolkit, "nightly");
+        assert_eq!(voter.name, "miri:nightly");
+    }
+
+    #[test]
+    fn sanitizer_voters_name_their_sanitizer() {
+        let voter = parse_voter("tsan:nightly-2021-06-02");
+        assert_eq!(voter.kind, VoterKind::Sanitizer(Sanitizer::Thread));
+        assert_eq!(voter.toolkit, "nightly-2021-06-02");
+    }
+
+    #[test]
+    fn backend_voters_name_their_backend() {
+        assert_eq!(
+            parse_voter("cranelift:nightly").kind,
+            VoterKind::Backend("cranelift".to_string())
+        );
+        let voter = parse_voter("gcc=/opt/cg_gcc/librustc_codegen_gcc.so:nightly");
+        assert_eq!(
+            voter.kind,
+            VoterKind::Backend("/opt/cg_gcc/librustc_codegen_gcc.so".to_string())
+        );
+        assert_eq!(voter.toolkit, "nightly");
+    }
+}
@@ -0,0 +1,103 @@
+use super::Voter;
+use crate::build::append_flags;
+use crate::runner::select_runner;
+use crate::{referendum_dir, toolkit_command, toolkit_dir};
+use crate::{ReferendumError, Result, RunOptions, Test};
+
+//rustc loads the backend before printing anything, so printing the cfg checks it can be used
+pub fn preflight(voter: &Voter, backend: &str) -> Result<()> {
+    let output = toolkit_command(&voter.toolkit, "rustc")
+        .arg(format!("-Zcodegen-backend={}", backend))
+        .arg("--print")
+        .arg("cfg")
+        .output()
+        .map_err(|e| ReferendumError::VoterUnavailable(voter.name.clone(), e.to_string()))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let error = stderr
+        .lines()
+        .next()
+        .unwrap_or("")
+        .trim()
+        .trim_start_matches("error: ")
+        .trim_end_matches(':')
+        .to_string();
+    let hint = match backend {
+        "cranelift" => format!(
+            ", run `rustup component add --toolchain {} rustc-codegen-cranelift-preview`",
+            voter.toolkit
+        ),
+        _ => String::new(),
+    };
+    Err(ReferendumError::VoterUnavailable(
+        voter.name.clone(),
+        format!("{}{}", error, hint),
+    ))
+}
+
+//each backend gets its own target directory, artifacts from different backends must not mix
+pub fn backend_options(voter: &Voter, backend: &str, options: &RunOptions) -> RunOptions {
+    let mut options = options.clone();
+    options.env.push((
+        "CARGO_TARGET_DIR".to_string(),
+        referendum_dir()
+            .join("backend")
+            .join(toolkit_dir(&voter.name))
+            .to_string_lossy()
+            .to_string(),
+    ));
+    let rustflags = append_flags(
+        &options,
+        "RUSTFLAGS",
+        &format!("-Zcodegen-backend={}", backend),
+    );
+    options.env.push(rustflags);
+    options
+}
+
+pub fn get_backend_tests(voter: &Voter, backend: &str, options: &RunOptions) -> Result<Vec<Test>> {
+    let options = backend_options(voter, backend, options);
+    select_runner(&voter.toolkit, options.runner).get_tests(&voter.name, &options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voter::parse_voter;
+
+    #[test]
+    fn backend_is_passed_to_rustc() {
+        let voter = parse_voter("gcc=/opt/cg_gcc/librustc_codegen_gcc.so:nightly");
+        let options = backend_options(
+            &voter,
+            "/opt/cg_gcc/librustc_codegen_gcc.so",
+            &RunOptions::default(),
+        );
+        assert!(options.env[0]
+            .1
+            .ends_with("gcc--opt-cg_gcc-librustc_codegen_gcc.so-nightly"));
+        assert_eq!(
+            options.env[1],
+            (
+                "RUSTFLAGS".to_string(),
+                "-Zcodegen-backend=/opt/cg_gcc/librustc_codegen_gcc.so".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn backend_flag_extends_existing_rustflags() {
+        let voter = parse_voter("cranelift:nightly");
+        let options = RunOptions {
+            env: vec![("RUSTFLAGS".to_string(), "-Ctarget-cpu=native".to_string())],
+            ..Default::default()
+        };
+        let options = backend_options(&voter, "cranelift", &options);
+        assert_eq!(
+            options.env.last().unwrap().1,
+            "-Ctarget-cpu=native -Zcodegen-backend=cranelift"
+        );
+    }
+}
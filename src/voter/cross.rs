@@ -0,0 +1,150 @@
+use super::Voter;
+use crate::build::configure;
+use crate::runner::{cargo_test_args, check_test_run};
+use crate::{parse_tests, parse_toolkit, toolkit_command, Toolkit};
+use crate::{ReferendumError, Result, RunOptions, Test};
+use std::env;
+use std::path::{Path, PathBuf};
+
+fn is_target_spec(target: &str) -> bool {
+    target.ends_with(".json")
+}
+
+//the variable cargo reads the runner from, e.g. CARGO_TARGET_AARCH64_UNKNOWN_LINUX_GNU_RUNNER,
+//custom targets are named after their specification file
+pub fn runner_variable(target: &str) -> String {
+    let name = match is_target_spec(target) {
+        true => Path::new(target)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(target),
+        false => target,
+    };
+    format!(
+        "CARGO_TARGET_{}_RUNNER",
+        name.to_uppercase().replace(['-', '.'], "_")
+    )
+}
+
+fn on_path(program: &str) -> bool {
+    if Path::new(program).components().count() > 1 {
+        return Path::new(program).is_file();
+    }
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+fn cross_error(voter: &Voter, e: impl std::fmt::Display) -> ReferendumError {
+    ReferendumError::VoterUnavailable(voter.name.clone(), e.to_string())
+}
+
+//a custom target has no prebuilt standard library, it is built with -Zbuild-std or the crate is no_std
+fn check_target(voter: &Voter, target: &str) -> Result<()> {
+    if is_target_spec(target) {
+        return match Path::new(target).is_file() {
+            true => Ok(()),
+            false => Err(cross_error(
+                voter,
+                format!("target specification {} does not exist", target),
+            )),
+        };
+    }
+    let output = toolkit_command(&voter.toolkit, "rustc")
+        .arg("--print")
+        .arg("sysroot")
+        .output()
+        .map_err(|e| cross_error(voter, e))?;
+    let sysroot = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    if sysroot.join("lib/rustlib").join(target).is_dir() {
+        return Ok(());
+    }
+    Err(cross_error(
+        voter,
+        match parse_toolkit(&voter.toolkit) {
+            Toolkit::Rustup(_) => format!(
+                "run `rustup target add --toolchain {} {}`",
+                voter.toolkit, target
+            ),
+            Toolkit::Paths { .. } => format!(
+                "the standard library for {} is not in {}",
+                target,
+                sysroot.display()
+            ),
+        },
+    ))
+}
+
+pub fn preflight(voter: &Voter) -> Result<()> {
+    check_target(voter, voter.target.as_deref().unwrap_or_default())?;
+    //without a runner on the command line cargo falls back to the environment and .cargo/config
+    let program = voter
+        .runner
+        .as_deref()
+        .and_then(|runner| runner.split_whitespace().next());
+    match program {
+        Some(program) if !on_path(program) => Err(cross_error(
+            voter,
+            format!("runner {} is not installed", program),
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub fn get_cross_tests(voter: &Voter, options: &RunOptions) -> Result<Vec<Test>> {
+    let target = voter.target.as_deref().unwrap_or_default();
+    let mut command = toolkit_command(&voter.toolkit, "cargo");
+    command
+        .arg("test")
+        .arg("--target")
+        .arg(target)
+        .args(cargo_test_args(options.filter.as_deref()));
+    configure(&mut command, options);
+    if let Some(runner) = &voter.runner {
+        command.env(runner_variable(target), runner);
+    }
+    let output = command
+        .output()
+        .map_err(|_| ReferendumError::TestRunFailure())?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    check_test_run(
+        output.status.success(),
+        &stdout,
+        &String::from_utf8_lossy(&output.stderr),
+    )?;
+    parse_tests(&voter.name, &stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voter::parse_voter;
+
+    #[test]
+    fn runner_variable_follows_cargo() {
+        assert_eq!(
+            runner_variable("aarch64-unknown-linux-gnu"),
+            "CARGO_TARGET_AARCH64_UNKNOWN_LINUX_GNU_RUNNER"
+        );
+        assert_eq!(
+            runner_variable("thumbv7em-none-eabihf.json"),
+            "CARGO_TARGET_THUMBV7EM_NONE_EABIHF_RUNNER"
+        );
+        assert_eq!(
+            runner_variable("targets/thumbv7em-none-eabihf.json"),
+            "CARGO_TARGET_THUMBV7EM_NONE_EABIHF_RUNNER"
+        );
+    }
+
+    #[test]
+    fn missing_target_specifications_are_reported() {
+        let voter = parse_voter("stable@targets/missing-none-eabi.json");
+        match preflight(&voter) {
+            Err(ReferendumError::VoterUnavailable(_, reason)) => assert_eq!(
+                reason,
+                "target specification targets/missing-none-eabi.json does not exist"
+            ),
+            other => panic!("unexpected preflight result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use super::Voter;
+use crate::build::{configure, parse_test_executables};
+use crate::runner::parse_test_list;
+use crate::{hash_outcome, parse_tests, toolkit_command, Finding};
+use crate::{ReferendumError, Result, RunOptions, Test};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+//checked outcomes are described by their findings, raw reports carry addresses and pids that change every run
+pub fn findings_test(voter: &Voter, name: &str, findings: Vec<Finding>) -> Test {
+    let output = findings
+        .iter()
+        .map(|finding| format!("{}: {} {}", finding.tool, finding.kind, finding.message))
+        .collect::<Vec<String>>()
+        .join("\n");
+    Test {
+        name: name.to_string(),
+        toolkit: voter.name.clone(),
+        result: false,
+        hash: hash_outcome(false, &output),
+        output,
+        findings,
+        ..Default::default()
+    }
+}
+
+pub(crate) fn build_test_executables(
+    toolkit: &str,
+    args: &[&str],
+    options: &RunOptions,
+) -> Result<Vec<PathBuf>> {
+    let mut command = toolkit_command(toolkit, "cargo");
+    command
+        .arg("test")
+        .arg("--no-run")
+        .arg("--message-format=json")
+        .args(args);
+    configure(&mut command, options);
+    let output = command
+        .output()
+        .map_err(|e| ReferendumError::BuildFailure(e.to_string()))?;
+    if !output.status.success() {
+        return Err(ReferendumError::BuildFailure(format!(
+            "cargo test --no-run {} failed with {}",
+            args.join(" "),
+            toolkit
+        )));
+    }
+    Ok(parse_test_executables(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+fn list_tests(executable: &Path, options: &RunOptions) -> Result<Vec<String>> {
+    let mut command = Command::new(executable);
+    command.arg("--list").arg("--format=terse");
+    configure(&mut command, options);
+    let output = command
+        .output()
+        .map_err(|_| ReferendumError::TestRunFailure())?;
+    Ok(parse_test_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn run_test(
+    voter: &Voter,
+    mut command: Command,
+    name: &str,
+    parse_findings: fn(&str) -> Vec<Finding>,
+) -> Result<Test> {
+    let output = command
+        .arg(name)
+        .arg("--exact")
+        .arg("--test-threads=1")
+        .arg("--show-output")
+        .output()
+        .map_err(|_| ReferendumError::TestRunFailure())?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let findings = parse_findings(&String::from_utf8_lossy(&output.stderr));
+    if !findings.is_empty() {
+        return Ok(findings_test(voter, name, findings));
+    }
+    let parsed = parse_tests(&voter.name, &stdout)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|test| test.name == name);
+    //a test that died without a report, e.g. on a signal, still gets a vote
+    Ok(parsed.unwrap_or_else(|| {
+        let result = output.status.success();
+        let stdout = match result {
+            true => String::new(),
+            false => format!("exited with {}", output.status),
+        };
+        Test {
+            name: name.to_string(),
+            toolkit: voter.name.clone(),
+            result,
+            hash: hash_outcome(result, &stdout),
+            output: stdout,
+            ..Default::default()
+        }
+    }))
+}
+
+//a checker may abort the harness or only report at exit, so every test runs alone in its own process
+pub(crate) fn run_isolated(
+    voter: &Voter,
+    executables: &[PathBuf],
+    options: &RunOptions,
+    command_for: impl Fn(&Path) -> Command,
+    parse_findings: fn(&str) -> Vec<Finding>,
+) -> Result<Vec<Test>> {
+    let mut tests = Vec::new();
+    for executable in executables.iter() {
+        for name in list_tests(executable, options)? {
+            if let Some(filter) = &options.filter {
+                if !name.contains(filter.as_str()) {
+                    continue;
+                }
+            }
+            let mut command = command_for(executable);
+            configure(&mut command, options);
+            tests.push(run_test(voter, command, &name, parse_findings)?);
+        }
+    }
+    Ok(tests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voter::parse_voter;
+
+    #[test]
+    fn findings_fail_the_test() {
+        let voter = parse_voter("asan:nightly");
+        let test = findings_test(
+            &voter,
+            "tests::a",
+            vec![Finding {
+                tool: "AddressSanitizer".to_string(),
+                kind: "heap-use-after-free".to_string(),
+                message: "/work/src/lib.rs:8:26 in tests::a".to_string(),
+            }],
+        );
+        assert!(!test.result);
+        assert_eq!(
+            test.output,
+            "AddressSanitizer: heap-use-after-free /work/src/lib.rs:8:26 in tests::a"
+        );
+    }
+}
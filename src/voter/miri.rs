@@ -0,0 +1,253 @@
+use super::Voter;
+use crate::build::configure;
+use crate::runner::parse_test_list;
+use crate::{hash_outcome, parse_tests, toolkit_command, Finding, ReferendumError, Result};
+use crate::{RunOptions, Test};
+use regex::Regex;
+
+pub fn preflight(voter: &Voter) -> Result<()> {
+    let installed = toolkit_command(&voter.toolkit, "cargo")
+        .arg("miri")
+        .arg("--version")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+    match installed {
+        true => Ok(()),
+        false => Err(ReferendumError::VoterUnavailable(
+            voter.name.clone(),
+            format!(
+                "run `rustup component add --toolchain {} miri`",
+                voter.toolkit
+            ),
+        )),
+    }
+}
+
+//the test that was started but never finished, miri aborts the whole harness on UB
+pub fn parse_aborted_test(stdout: &str) -> Option<String> {
+    let re = Regex::new(r"^test (\S+) \.\.\.\s*$").unwrap();
+    stdout
+        .lines()
+        .last()
+        .and_then(|line| re.captures(line))
+        .map(|caps| caps[1].to_string())
+}
+
+//the undefined behavior message and the report around it, up to miri's abort
+pub fn parse_ub_report(stderr: &str) -> Option<(String, String)> {
+    let lines: Vec<&str> = stderr.lines().collect();
+    let start = lines
+        .iter()
+        .position(|line| line.starts_with("error: Undefined Behavior:"))?;
+    let end = lines[start..]
+        .iter()
+        .position(|line| line.starts_with("error: aborting"))
+        .map(|end| start + end)
+        .unwrap_or(lines.len());
+    let message = lines[start]
+        .trim_start_matches("error: Undefined Behavior:")
+        .trim()
+        .to_string();
+    Some((message, lines[start..end].join("\n").trim_end().to_string()))
+}
+
+pub fn ub_test(voter: &Voter, name: &str, message: String, report: String) -> Test {
+    Test {
+        name: name.to_string(),
+        toolkit: voter.name.clone(),
+        result: false,
+        hash: hash_outcome(false, &report),
+        output: report,
+        findings: vec![Finding {
+            tool: "miri".to_string(),
+            kind: "Undefined Behavior".to_string(),
+            message,
+        }],
+        ..Default::default()
+    }
+}
+
+//libtest matches skips as substrings, so once a test is skipped the run is `--exact`, and the filter is replaced by the exact names it matched
+pub fn miri_test_args(filter: Option<&str>, matched: &[String], skipped: &[String]) -> Vec<String> {
+    let mut args = Vec::new();
+    if skipped.is_empty() {
+        args.extend(filter.map(|filter| filter.to_string()));
+    }
+    args.extend(
+        ["--", "--test-threads=1", "--show-output"]
+            .iter()
+            .map(|arg| arg.to_string()),
+    );
+    if !skipped.is_empty() {
+        args.extend(matched.iter().cloned());
+        args.push("--exact".to_string());
+    }
+    for name in skipped.iter() {
+        args.push("--skip".to_string());
+        args.push(name.clone());
+    }
+    args
+}
+
+fn list_miri_tests(voter: &Voter, filter: &str, options: &RunOptions) -> Result<Vec<String>> {
+    let mut command = toolkit_command(&voter.toolkit, "cargo");
+    command
+        .arg("miri")
+        .arg("test")
+        .arg(filter)
+        .arg("--")
+        .arg("--list")
+        .arg("--format=terse");
+    configure(&mut command, options);
+    let output = command
+        .output()
+        .map_err(|_| ReferendumError::TestRunFailure())?;
+    Ok(parse_test_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+//each UB aborts the run, so the aborted test is skipped and the remaining tests run again
+pub fn get_miri_tests(voter: &Voter, options: &RunOptions) -> Result<Vec<Test>> {
+    let mut ub_tests: Vec<Test> = Vec::new();
+    let mut matched: Vec<String> = Vec::new();
+    let tests = loop {
+        let skipped: Vec<String> = ub_tests.iter().map(|test| test.name.clone()).collect();
+        if let (Some(filter), false) = (&options.filter, skipped.is_empty()) {
+            if matched.is_empty() {
+                matched = list_miri_tests(voter, filter, options)?;
+            }
+            //without a positional filter the rerun would run every test
+            if matched.iter().all(|name| skipped.contains(name)) {
+                break ub_tests;
+            }
+        }
+        let mut command = toolkit_command(&voter.toolkit, "cargo");
+        command.arg("miri").arg("test").args(miri_test_args(
+            options.filter.as_deref(),
+            &matched,
+            &skipped,
+        ));
+        configure(&mut command, options);
+        let output = command
+            .output()
+            .map_err(|_| ReferendumError::TestRunFailure())?;
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+
+        match (parse_aborted_test(&stdout), parse_ub_report(&stderr)) {
+            (Some(name), Some((message, report))) => {
+                ub_tests.push(ub_test(voter, &name, message, report));
+            }
+            _ if output.status.success() || stdout.contains("test result:") => {
+                let mut tests = parse_tests(&voter.name, &stdout)?;
+                tests.extend(ub_tests);
+                break tests;
+            }
+            _ => return Err(ReferendumError::TestRunFailure()),
+        }
+    };
+    Ok(match &options.filter {
+        Some(filter) => tests
+            .into_iter()
+            .filter(|test| test.name.contains(filter.as_str()))
+            .collect(),
+        None => tests,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voter::parse_voter;
+
+    const STDOUT: &str = "
+running 3 tests
+test tests::adds ... ok
+test tests::dangling ... ";
+
+    const STDERR: &str = "error: Undefined Behavior: memory access failed: alloc1 has been freed, so this pointer is dangling
+  --> src/lib.rs:12:18
+   |
+12 |         unsafe { *ptr }
+   |                  ^^^^ memory access failed
+   |
+note: some details are omitted, run with `MIRIFLAGS=-Zmiri-backtrace=full` for a verbose backtrace
+
+error: aborting due to 1 previous error
+
+error: test failed, to rerun pass `--lib`";
+
+    #[test]
+    fn aborted_test_is_the_unfinished_one() {
+        assert_eq!(
+            parse_aborted_test(STDOUT),
+            Some("tests::dangling".to_string())
+        );
+        assert_eq!(parse_aborted_test("test tests::adds ... ok\n"), None);
+    }
+
+    #[test]
+    fn ub_report_is_captured() {
+        let (message, report) = parse_ub_report(STDERR).unwrap();
+        assert_eq!(
+            message,
+            "memory access failed: alloc1 has been freed, so this pointer is dangling"
+        );
+        assert!(report.starts_with("error: Undefined Behavior:"));
+        assert!(report.ends_with("for a verbose backtrace"));
+        assert!(parse_ub_report("test result: ok").is_none());
+    }
+
+    #[test]
+    fn skips_do_not_drop_tests_they_prefix() {
+        assert_eq!(
+            miri_test_args(None, &[], &[]),
+            vec!["--", "--test-threads=1", "--show-output"]
+        );
+        //skipping tests::a must leave tests::ab to run
+        assert_eq!(
+            miri_test_args(None, &[], &["tests::a".to_string()]),
+            vec![
+                "--",
+                "--test-threads=1",
+                "--show-output",
+                "--exact",
+                "--skip",
+                "tests::a"
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_keep_the_run_scoped_to_the_filter() {
+        let matched = vec!["tests::a".to_string(), "tests::ab".to_string()];
+        assert_eq!(
+            miri_test_args(Some("tests::a"), &matched, &[]),
+            vec!["tests::a", "--", "--test-threads=1", "--show-output"]
+        );
+        assert_eq!(
+            miri_test_args(Some("tests::a"), &matched, &["tests::a".to_string()]),
+            vec![
+                "--",
+                "--test-threads=1",
+                "--show-output",
+                "tests::a",
+                "tests::ab",
+                "--exact",
+                "--skip",
+                "tests::a"
+            ]
+        );
+    }
+
+    #[test]
+    fn ub_is_a_distinct_failing_outcome() {
+        let voter = parse_voter("miri:nightly");
+        let (message, report) = parse_ub_report(STDERR).unwrap();
+        let test = ub_test(&voter, "tests::dangling", message, report);
+        assert!(!test.result);
+        assert_eq!(test.toolkit, "miri:nightly");
+        assert_eq!(test.findings[0].kind, "Undefined Behavior");
+        assert_ne!(test.hash, hash_outcome(false, ""));
+    }
+}
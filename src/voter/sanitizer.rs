@@ -0,0 +1,248 @@
+use super::isolated::{build_test_executables, run_isolated};
+use super::Voter;
+use crate::build::append_flags;
+use crate::toolchain::get_toolchain;
+use crate::{
+    referendum_dir, toolkit_command, toolkit_dir, Finding, ReferendumError, Result, RunOptions,
+    Test,
+};
+use regex::Regex;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sanitizer {
+    Address,
+    Memory,
+    Thread,
+    Leak,
+}
+
+impl Sanitizer {
+    pub fn from_prefix(prefix: &str) -> Option<Sanitizer> {
+        match prefix {
+            "asan" => Some(Sanitizer::Address),
+            "msan" => Some(Sanitizer::Memory),
+            "tsan" => Some(Sanitizer::Thread),
+            "lsan" => Some(Sanitizer::Leak),
+            _ => None,
+        }
+    }
+
+    pub fn rustflags(&self) -> &'static str {
+        match self {
+            Sanitizer::Address => "-Zsanitizer=address",
+            Sanitizer::Memory => "-Zsanitizer=memory -Zsanitizer-memory-track-origins",
+            Sanitizer::Thread => "-Zsanitizer=thread",
+            Sanitizer::Leak => "-Zsanitizer=leak",
+        }
+    }
+
+    //an uninstrumented std gives false positives under msan and misses races under tsan
+    pub fn needs_build_std(&self) -> bool {
+        matches!(self, Sanitizer::Memory | Sanitizer::Thread)
+    }
+}
+
+fn sanitizer_error(voter: &Voter, e: impl std::fmt::Display) -> ReferendumError {
+    ReferendumError::VoterUnavailable(voter.name.clone(), e.to_string())
+}
+
+pub fn preflight(voter: &Voter, sanitizer: Sanitizer) -> Result<()> {
+    let toolchain = get_toolchain(&voter.toolkit)?;
+    if !toolchain.release.contains("nightly") && !toolchain.release.contains("-dev") {
+        return Err(sanitizer_error(voter, "sanitizers need a nightly toolkit"));
+    }
+    if !sanitizer.needs_build_std() {
+        return Ok(());
+    }
+    let output = toolkit_command(&voter.toolkit, "rustc")
+        .arg("--print")
+        .arg("sysroot")
+        .output()
+        .map_err(|e| sanitizer_error(voter, e))?;
+    let sysroot = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    match sysroot.join("lib/rustlib/src/rust/library").is_dir() {
+        true => Ok(()),
+        false => Err(sanitizer_error(
+            voter,
+            format!(
+                "-Zbuild-std needs the standard library source, run `rustup component add --toolchain {} rust-src`",
+                voter.toolkit
+            ),
+        )),
+    }
+}
+
+//findings in report order, the kind comes from the ERROR/WARNING header and the location from the SUMMARY line
+pub fn parse_sanitizer_reports(stderr: &str) -> Vec<Finding> {
+    let header = Regex::new(r"(?:ERROR|WARNING): (\w+Sanitizer): (.*)").unwrap();
+    let summary = Regex::new(r"^SUMMARY: \w+Sanitizer: (.*)").unwrap();
+    let headers: Vec<(String, String)> = stderr
+        .lines()
+        .filter_map(|line| header.captures(line))
+        .map(|caps| {
+            let kind = caps[2]
+                .split(" on ")
+                .next()
+                .unwrap_or("")
+                .split(" (")
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_string();
+            (caps[1].to_string(), kind)
+        })
+        .collect();
+    let summaries: Vec<String> = stderr
+        .lines()
+        .filter_map(|line| summary.captures(line))
+        .map(|caps| caps[1].trim().to_string())
+        .collect();
+    headers
+        .into_iter()
+        .enumerate()
+        .map(|(i, (tool, kind))| {
+            let message = match summaries.get(i) {
+                Some(text) => text.strip_prefix(&kind).unwrap_or(text).trim().to_string(),
+                None => String::new(),
+            };
+            Finding {
+                tool,
+                kind,
+                message,
+            }
+        })
+        .collect()
+}
+
+fn sanitizer_options(voter: &Voter, sanitizer: Sanitizer, options: &RunOptions) -> RunOptions {
+    let mut options = options.clone();
+    options.env.push((
+        "CARGO_TARGET_DIR".to_string(),
+        referendum_dir()
+            .join("sanitizer")
+            .join(toolkit_dir(&voter.name))
+            .to_string_lossy()
+            .to_string(),
+    ));
+    let rustflags = append_flags(&options, "RUSTFLAGS", sanitizer.rustflags());
+    let rustdocflags = append_flags(&options, "RUSTDOCFLAGS", sanitizer.rustflags());
+    options.env.push(rustflags);
+    options.env.push(rustdocflags);
+    options
+}
+
+//sanitizers need an explicit --target so build scripts and proc macros stay uninstrumented
+pub fn get_sanitizer_tests(
+    voter: &Voter,
+    sanitizer: Sanitizer,
+    options: &RunOptions,
+) -> Result<Vec<Test>> {
+    let options = sanitizer_options(voter, sanitizer, options);
+    let host = get_toolchain(&voter.toolkit)?.host;
+    let mut args = vec!["--target", host.as_str()];
+    if sanitizer.needs_build_std() {
+        args.push("-Zbuild-std");
+    }
+    let executables = build_test_executables(&voter.toolkit, &args, &options)?;
+    run_isolated(
+        voter,
+        &executables,
+        &options,
+        |executable| Command::new(executable),
+        parse_sanitizer_reports,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voter::isolated::findings_test;
+    use crate::voter::parse_voter;
+
+    const ASAN: &str = "test tests::dangling ... =================================================================
+==24660==ERROR: AddressSanitizer: heap-use-after-free on address 0x7b68ebfe4010 at pc 0x55f82275c28d bp 0x7b48e99fe360 sp 0x7b48e99fe358
+READ of size 4 at 0x7b68ebfe4010 thread T1
+    #0 0x55f82275c28c in core::ptr::read_volatile::<u32> /rustc/library/core/src/ptr/mod.rs:1742:14
+SUMMARY: AddressSanitizer: heap-use-after-free /work/src/lib.rs:8:26 in uaf::tests::dangling
+==24660==ABORTING";
+
+    const TSAN: &str = "==================
+WARNING: ThreadSanitizer: data race (pid=4711)
+  Write of size 4 at 0x7b0400000000 by thread T2:
+SUMMARY: ThreadSanitizer: data race /work/src/lib.rs:14:9 in race::bump
+==================
+ThreadSanitizer: reported 1 warnings";
+
+    const LSAN: &str = "==812==ERROR: LeakSanitizer: detected memory leaks
+
+Direct leak of 4 byte(s) in 1 object(s) allocated from:
+    #0 0x5555 in malloc
+
+SUMMARY: LeakSanitizer: 4 byte(s) leaked in 1 allocation(s).";
+
+    #[test]
+    fn sanitizer_prefixes() {
+        assert_eq!(Sanitizer::from_prefix("msan"), Some(Sanitizer::Memory));
+        assert_eq!(Sanitizer::from_prefix("miri"), None);
+        assert!(Sanitizer::Thread.needs_build_std());
+        assert!(!Sanitizer::Address.needs_build_std());
+    }
+
+    #[test]
+    fn reports_become_findings() {
+        assert_eq!(
+            parse_sanitizer_reports(ASAN),
+            vec![Finding {
+                tool: "AddressSanitizer".to_string(),
+                kind: "heap-use-after-free".to_string(),
+                message: "/work/src/lib.rs:8:26 in uaf::tests::dangling".to_string(),
+            }]
+        );
+        assert_eq!(parse_sanitizer_reports(TSAN)[0].kind, "data race");
+        assert_eq!(
+            parse_sanitizer_reports(TSAN)[0].message,
+            "/work/src/lib.rs:14:9 in race::bump"
+        );
+        assert_eq!(
+            parse_sanitizer_reports(LSAN)[0].kind,
+            "detected memory leaks"
+        );
+        assert_eq!(
+            parse_sanitizer_reports(LSAN)[0].message,
+            "4 byte(s) leaked in 1 allocation(s)."
+        );
+        assert!(parse_sanitizer_reports("test result: ok").is_empty());
+    }
+
+    #[test]
+    fn sanitized_outcomes_ignore_addresses() {
+        let voter = parse_voter("asan:nightly");
+        let first = findings_test(&voter, "tests::dangling", parse_sanitizer_reports(ASAN));
+        let again = findings_test(
+            &voter,
+            "tests::dangling",
+            parse_sanitizer_reports(&ASAN.replace("0x7b68ebfe4010", "0x7c00aa000010")),
+        );
+        assert!(!first.result);
+        assert_eq!(first.hash, again.hash);
+    }
+
+    #[test]
+    fn sanitizer_flags_extend_existing_rustflags() {
+        let voter = parse_voter("asan:nightly");
+        let options = RunOptions {
+            env: vec![("RUSTFLAGS".to_string(), "--cfg loom".to_string())],
+            ..Default::default()
+        };
+        let options = sanitizer_options(&voter, Sanitizer::Address, &options);
+        let rustflags = options
+            .env
+            .iter()
+            .rev()
+            .find(|(key, _)| key == "RUSTFLAGS")
+            .unwrap();
+        assert_eq!(rustflags.1, "--cfg loom -Zsanitizer=address");
+    }
+}
@@ -0,0 +1,147 @@
+use super::isolated::{build_test_executables, run_isolated};
+use super::Voter;
+use crate::{referendum_dir, toolkit_dir, Finding, ReferendumError, Result, RunOptions, Test};
+use regex::Regex;
+use std::process::Command;
+
+//only definite leaks are errors, std keeps some allocations alive until exit on purpose
+const MEMCHECK: [&str; 4] = [
+    "--tool=memcheck",
+    "--leak-check=full",
+    "--show-leak-kinds=definite",
+    "--errors-for-leak-kinds=definite",
+];
+
+pub fn preflight(voter: &Voter) -> Result<()> {
+    let installed = Command::new("valgrind")
+        .arg("--version")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+    match installed {
+        true => Ok(()),
+        false => Err(ReferendumError::VoterUnavailable(
+            voter.name.clone(),
+            "valgrind is not installed".to_string(),
+        )),
+    }
+}
+
+//memcheck errors, named by their header and located at the first frame outside the allocator
+pub fn parse_memcheck_errors(stderr: &str) -> Vec<Finding> {
+    let prefix = Regex::new(r"^==\d+== ?").unwrap();
+    let header = Regex::new(concat!(
+        r"^(Invalid (?:read|write) of size \d+",
+        r"|Invalid free\(\).*",
+        r"|Mismatched free\(\).*",
+        r"|Conditional jump or move depends on uninitialised value\(s\)",
+        r"|Use of uninitialised value of size \d+",
+        r"|Syscall param \S+ (?:points to|contains) uninitialised byte\(s\)",
+        r"|Source and destination overlap in \S+",
+        r"|[\d,]+ bytes in [\d,]+ blocks are definitely lost)"
+    ))
+    .unwrap();
+    let frame = Regex::new(r"^\s+(?:at|by) 0x[0-9A-Fa-f]+: (.*)").unwrap();
+    let lines: Vec<String> = stderr
+        .lines()
+        .filter(|line| prefix.is_match(line))
+        .map(|line| prefix.replace(line, "").to_string())
+        .collect();
+
+    let mut findings = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let kind = match header.captures(line) {
+            Some(caps) => caps[1].to_string(),
+            None => continue,
+        };
+        let message = lines[i + 1..]
+            .iter()
+            .map_while(|line| frame.captures(line))
+            .map(|caps| caps[1].to_string())
+            .find(|location| {
+                !location.contains("vg_replace_")
+                    && !location.contains("vgpreload_")
+                    && !location.starts_with("alloc::")
+            })
+            .unwrap_or_default();
+        findings.push(Finding {
+            tool: "memcheck".to_string(),
+            kind,
+            message,
+        });
+    }
+    findings
+}
+
+pub fn get_valgrind_tests(voter: &Voter, options: &RunOptions) -> Result<Vec<Test>> {
+    let mut options = options.clone();
+    options.env.push((
+        "CARGO_TARGET_DIR".to_string(),
+        referendum_dir()
+            .join("valgrind")
+            .join(toolkit_dir(&voter.toolkit))
+            .to_string_lossy()
+            .to_string(),
+    ));
+    let executables = build_test_executables(&voter.toolkit, &[], &options)?;
+    run_isolated(
+        voter,
+        &executables,
+        &options,
+        |executable| {
+            let mut command = Command::new("valgrind");
+            command.args(MEMCHECK).arg(executable);
+            command
+        },
+        parse_memcheck_errors,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMCHECK_OUTPUT: &str = "==4242== Memcheck, a memory error detector
+==4242== Command: target/debug/deps/uaf-0123 tests::dangling --exact
+==4242==
+==4242== Invalid read of size 4
+==4242==    at 0x10C2A1: uaf::tests::dangling (lib.rs:8)
+==4242==    by 0x10C1F9: uaf::tests::dangling::{{closure}} (lib.rs:4)
+==4242==  Address 0x4a8e040 is 0 bytes inside a block of size 4 free'd
+==4242==    at 0x483CA3F: free (vg_replace_malloc.c:540)
+==4242==
+==4242== 16 bytes in 1 blocks are definitely lost in loss record 1 of 2
+==4242==    at 0x483B7F3: malloc (in /usr/lib/valgrind/vgpreload_memcheck-amd64-linux.so)
+==4242==    by 0x10D0A2: alloc::alloc::alloc (alloc.rs:93)
+==4242==    by 0x10C3B4: uaf::tests::leaks (lib.rs:15)
+==4242==
+==4242== ERROR SUMMARY: 2 errors from 2 contexts (suppressed: 0 from 0)";
+
+    #[test]
+    fn memcheck_errors_become_findings() {
+        let findings = parse_memcheck_errors(MEMCHECK_OUTPUT);
+        assert_eq!(
+            findings,
+            vec![
+                Finding {
+                    tool: "memcheck".to_string(),
+                    kind: "Invalid read of size 4".to_string(),
+                    message: "uaf::tests::dangling (lib.rs:8)".to_string(),
+                },
+                Finding {
+                    tool: "memcheck".to_string(),
+                    kind: "16 bytes in 1 blocks are definitely lost".to_string(),
+                    message: "uaf::tests::leaks (lib.rs:15)".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn clean_runs_have_no_findings() {
+        assert!(parse_memcheck_errors(
+            "==4242== ERROR SUMMARY: 0 errors from 0 contexts (suppressed: 0 from 0)"
+        )
+        .is_empty());
+    }
+}